@@ -0,0 +1,175 @@
+//! Read the files of tar and zip archives without extracting them.
+use crate::{
+    attributes, glob_matcher, is_selected, DirContents, Filter, Outcome, Reader, Root, Skipped,
+};
+use anyhow::{bail, Context};
+use prodash::Progress;
+use std::io::{BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The formats of archives which can be read.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+    Tar,
+    /// A tar archive compressed with gzip.
+    TarGz,
+    Zip,
+}
+
+/// The extensions of archives by their format, compared case-insensitively.
+const EXTENSIONS: [(&str, Kind); 4] = [
+    (".tar.gz", Kind::TarGz),
+    (".tgz", Kind::TarGz),
+    (".tar", Kind::Tar),
+    (".zip", Kind::Zip),
+];
+
+impl Kind {
+    /// Determine the format of the archive at `path` by its extension, or return `None` if it isn't an archive.
+    pub fn of(path: &Path) -> Option<Kind> {
+        split_extension(path).map(|(_, kind)| kind)
+    }
+}
+
+/// Split the file name of the archive at `path` into the name without its extension and its format.
+fn split_extension(path: &Path) -> Option<(&str, Kind)> {
+    let name = path.file_name()?.to_str()?;
+    EXTENSIONS.iter().find_map(|(extension, kind)| {
+        let name_len = name.len().checked_sub(extension.len())?;
+        name.get(name_len..)?
+            .eq_ignore_ascii_case(extension)
+            .then(|| (&name[..name_len], *kind))
+    })
+}
+
+/// Read all text files in the `archive` like [`crate::unicode_content()`] does, but keep their contents in memory.
+///
+/// Files are placed below the path of the archive, as if it was a directory, which is named after the archive
+/// without its extension. Ignore files and `.gitattributes` files in the archive don't apply, while the `filter` does.
+pub fn unicode_content(
+    archive: &Path,
+    filter: &Filter,
+    mut progress: impl Progress,
+    should_interrupt: &AtomicBool,
+) -> anyhow::Result<(DirContents, Skipped)> {
+    let start = std::time::Instant::now();
+    progress.init(None, Some(prodash::unit::label("files")));
+    let mut content_progress = progress.add_child("content");
+    content_progress.init(
+        None,
+        Some(prodash::unit::dynamic_and_mode(
+            prodash::unit::Bytes,
+            prodash::unit::display::Mode::with_throughput(),
+        )),
+    );
+
+    let (name, kind) = split_extension(archive).with_context(|| {
+        format!("{archive:?} is no archive, it must end with .tar, .tar.gz, .tgz or .zip")
+    })?;
+    let root = Root {
+        dir: archive.to_owned(),
+        name: name.to_owned(),
+    };
+
+    let include = glob_matcher(Path::new(""), &filter.include)?;
+    let exclude = glob_matcher(Path::new(""), &filter.exclude)?;
+    let mut reader = Reader::new(root);
+    let mut add = |relative_path: PathBuf, size: u64, entry: &mut dyn Read| -> anyhow::Result<()> {
+        if should_interrupt.load(Ordering::Relaxed) {
+            bail!("Cancelled by user")
+        }
+        progress.inc();
+        if !is_selected(&include, &exclude, &relative_path) {
+            return Ok(());
+        }
+        let declared = attributes::Declared::default();
+        if let Some(skipped) = filter
+            .skips_extension(&relative_path)
+            .or_else(|| filter.skips_before_reading(&declared, size))
+        {
+            reader.add(Outcome::skipped(skipped));
+            return Ok(());
+        }
+        let mut bytes = Vec::new();
+        entry
+            .take(filter.max_size())
+            .read_to_end(&mut bytes)
+            .with_context(|| format!("Failed to read {relative_path:?} from {archive:?}"))?;
+        content_progress.inc_by(bytes.len());
+        reader.add(Outcome::decode(
+            filter,
+            archive.join(&relative_path),
+            &relative_path,
+            declared,
+            size,
+            bytes,
+            false,
+        ));
+        Ok(())
+    };
+
+    let file = BufReader::new(
+        std::fs::File::open(archive).with_context(|| format!("Failed to open {archive:?}"))?,
+    );
+    match kind {
+        Kind::Tar => read_tar(tar::Archive::new(file), &mut add),
+        Kind::TarGz => read_tar(
+            tar::Archive::new(flate2::read::GzDecoder::new(file)),
+            &mut add,
+        ),
+        Kind::Zip => read_zip(file, &mut add),
+    }
+    .with_context(|| format!("Failed to read archive {archive:?}"))?;
+
+    let contents = reader.finish(&mut progress);
+    progress.show_throughput(start);
+    content_progress.show_throughput(start);
+    Ok(contents)
+}
+
+/// A function receiving the path, size and content of each file in an archive.
+type AddEntry<'a> = dyn FnMut(PathBuf, u64, &mut dyn Read) -> anyhow::Result<()> + 'a;
+
+fn read_tar(mut archive: tar::Archive<impl Read>, add: &mut AddEntry) -> anyhow::Result<()> {
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let Some(path) = normalize(&entry.path()?) else {
+            continue;
+        };
+        let size = entry.size();
+        add(path, size, &mut entry)?;
+    }
+    Ok(())
+}
+
+fn read_zip(file: impl Read + std::io::Seek, add: &mut AddEntry) -> anyhow::Result<()> {
+    let mut archive = zip::ZipArchive::new(file)?;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        if !entry.is_file() {
+            continue;
+        }
+        let Some(path) = entry.enclosed_name().and_then(normalize) else {
+            continue;
+        };
+        let size = entry.size();
+        add(path, size, &mut entry)?;
+    }
+    Ok(())
+}
+
+/// Remove `.` components from `path`, or return `None` if it isn't relative or leaves the archive with `..`.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    path.components()
+        .filter(|component| component != &Component::CurDir)
+        .map(|component| match component {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        })
+        .collect::<Option<PathBuf>>()
+        .filter(|path| !path.as_os_str().is_empty())
+}
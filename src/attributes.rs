@@ -0,0 +1,166 @@
+//! Read the attributes of files from `.gitattributes` files which declare how files should be treated.
+use anyhow::Context;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// The attributes of a file which are relevant to us.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct Declared {
+    /// `Some(true)` if the file has `linguist-generated` set, or `Some(false)` if it is unset.
+    pub generated: Option<bool>,
+    /// `Some(true)` if the file has `linguist-vendored` set, or `Some(false)` if it is unset.
+    pub vendored: Option<bool>,
+    /// True if the file has the `binary` attribute set.
+    pub binary: bool,
+}
+
+impl Declared {
+    /// Return `Some(true)` if the file was declared generated or vendored, `Some(false)` if it was declared
+    /// as neither, or `None` if there is no declaration.
+    pub fn is_generated(&self) -> Option<bool> {
+        match (self.generated, self.vendored) {
+            (Some(true), _) | (_, Some(true)) => Some(true),
+            (None, None) => None,
+            _ => Some(false),
+        }
+    }
+}
+
+/// A pattern of a `.gitattributes` file along with the attributes it sets (`Some(true)`), unsets (`Some(false)`)
+/// or makes unspecified again (`None`).
+type Rule = (Gitignore, Vec<(String, Option<bool>)>);
+
+/// The `.gitattributes` files of a repository, which are read as they are needed, by any amount of threads.
+pub struct Attributes {
+    /// The root of the repository, or the input directory if it isn't in a repository.
+    root: PathBuf,
+    /// The input directory, canonicalized.
+    dir: PathBuf,
+    /// The rules of the `.gitattributes` file in each directory, which are read by the first thread needing them.
+    rules: Mutex<HashMap<PathBuf, Arc<OnceLock<Vec<Rule>>>>>,
+    /// Messages about invalid lines of `.gitattributes` files, which were skipped.
+    warnings: Mutex<Vec<String>>,
+}
+
+impl Attributes {
+    /// Prepare reading the attributes of files in `dir`, with `.gitattributes` files of parent directories
+    /// up to the root of the repository applying as well.
+    pub fn new(dir: &Path) -> anyhow::Result<Self> {
+        let dir = dir
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve {dir:?}"))?;
+        let root = dir
+            .ancestors()
+            .find(|dir| dir.join(".git").exists())
+            .unwrap_or(&dir)
+            .to_owned();
+        Ok(Attributes {
+            root,
+            dir,
+            rules: Default::default(),
+            warnings: Default::default(),
+        })
+    }
+
+    /// Return the attributes of the file at `relative_path`, relative to the input directory.
+    ///
+    /// Files in deeper directories take precedence over those in their parents, and later lines over earlier ones.
+    pub fn of(&self, relative_path: &Path) -> Declared {
+        let path = self.dir.join(relative_path);
+        let dirs: Vec<_> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(&self.root))
+            .map(ToOwned::to_owned)
+            .collect();
+
+        let mut declared = Declared::default();
+        for dir in dirs.into_iter().rev() {
+            let relative_path = path.strip_prefix(&dir).expect("ancestor");
+            // the map is only locked briefly, so threads only wait for each other to read the same file.
+            let rules = Arc::clone(
+                self.rules
+                    .lock()
+                    .expect("no panics while holding the lock")
+                    .entry(dir.clone())
+                    .or_default(),
+            );
+            for (pattern, attributes) in rules.get_or_init(|| read_rules(&dir, &self.warnings)) {
+                if !pattern.matched(relative_path, false).is_ignore() {
+                    continue;
+                }
+                for (name, value) in attributes {
+                    match name.as_str() {
+                        "linguist-generated" => declared.generated = *value,
+                        "linguist-vendored" => declared.vendored = *value,
+                        "binary" => declared.binary = *value == Some(true),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        declared
+    }
+
+    /// Return the messages about invalid lines of `.gitattributes` files read since the last call, which were
+    /// skipped like git does.
+    pub fn take_warnings(&self) -> Vec<String> {
+        std::mem::take(
+            &mut self
+                .warnings
+                .lock()
+                .expect("no panics while holding the lock"),
+        )
+    }
+}
+
+/// Read the rules of the `.gitattributes` file in `dir`, if there is one, skipping invalid lines after adding
+/// a message about them to `warnings`.
+fn read_rules(dir: &Path, warnings: &Mutex<Vec<String>>) -> Vec<Rule> {
+    let path = dir.join(".gitattributes");
+    let Ok(input) = std::fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    let mut rules = Vec::new();
+    for line in input.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let Some(pattern) = fields.next() else {
+            continue;
+        };
+        let attributes = fields
+            .map(|attribute| {
+                if let Some(name) = attribute.strip_prefix('-') {
+                    (name.to_owned(), Some(false))
+                } else if let Some(name) = attribute.strip_prefix('!') {
+                    (name.to_owned(), None)
+                } else if let Some((name, value)) = attribute.split_once('=') {
+                    (name.to_owned(), Some(!matches!(value, "false" | "0")))
+                } else {
+                    (attribute.to_owned(), Some(true))
+                }
+            })
+            .collect();
+
+        let mut builder = GitignoreBuilder::new(dir);
+        let rule = match builder.add_line(None, pattern) {
+            Ok(_) => builder.build(),
+            Err(err) => Err(err),
+        };
+        match rule {
+            Ok(rule) => rules.push((rule, attributes)),
+            Err(err) => warnings
+                .lock()
+                .expect("no panics while holding the lock")
+                .push(format!(
+                    "Skipped invalid pattern {pattern:?} in {path:?}: {err}"
+                )),
+        }
+    }
+    rules
+}
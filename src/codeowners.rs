@@ -0,0 +1,137 @@
+//! Determine the owners of files from `CODEOWNERS` files in GitHub or GitLab syntax.
+use anyhow::Context;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Path, PathBuf};
+
+/// The places a `CODEOWNERS` file is looked for, relative to the root of the repository.
+const LOCATIONS: [&str; 4] = [
+    ".github/CODEOWNERS",
+    ".gitlab/CODEOWNERS",
+    "docs/CODEOWNERS",
+    "CODEOWNERS",
+];
+
+/// The rules of a `CODEOWNERS` file.
+#[derive(Debug)]
+pub struct CodeOwners {
+    /// Each pattern along with its owners, in the order they appear in the file.
+    rules: Vec<(Gitignore, Vec<String>)>,
+    /// The directory the patterns are relative to, usually the root of the repository.
+    root: PathBuf,
+    /// The path of the rendered directory relative to the root of the repository.
+    prefix: PathBuf,
+}
+
+impl CodeOwners {
+    /// Find the `CODEOWNERS` file of the repository containing `dir` by looking in `dir` and all of its parents.
+    ///
+    /// Returns `None` if there is none.
+    pub fn discover(dir: &Path) -> anyhow::Result<Option<Self>> {
+        let dir = dir
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve {dir:?}"))?;
+        for root in dir.ancestors() {
+            for location in LOCATIONS {
+                let path = root.join(location);
+                if path.is_file() {
+                    let prefix = dir.strip_prefix(root).expect("ancestor").to_owned();
+                    return Self::from_path(&path, root, prefix).map(Some);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Read the `CODEOWNERS` file at `path`, whose patterns are relative to `root`,
+    /// where `prefix` is the path of the rendered directory relative to `root`.
+    pub fn from_path(path: &Path, root: &Path, prefix: PathBuf) -> anyhow::Result<Self> {
+        let input = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read CODEOWNERS at {path:?}"))?;
+        let mut rules = Vec::new();
+        let mut section_owners = Vec::new();
+        for line in input.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // GitLab sections look like `[Section name][2] @default-owner`, or `^[Optional section]`.
+            if let Some(section) = line.strip_prefix('^').unwrap_or(line).strip_prefix('[') {
+                section_owners = section
+                    .rsplit_once(']')
+                    .map(|(_, owners)| owners.split_whitespace().map(ToOwned::to_owned).collect())
+                    .unwrap_or_default();
+                continue;
+            }
+
+            let mut fields = split_unescaped_whitespace(line);
+            let pattern = fields.remove(0);
+            let mut owners: Vec<_> = fields
+                .into_iter()
+                .take_while(|owner| !owner.starts_with('#'))
+                .collect();
+            if owners.is_empty() {
+                owners = section_owners.clone();
+            }
+
+            let mut builder = GitignoreBuilder::new(root);
+            builder
+                .add_line(None, &pattern)
+                .with_context(|| format!("Invalid CODEOWNERS pattern {pattern:?}"))?;
+            rules.push((builder.build()?, owners));
+        }
+        Ok(CodeOwners {
+            rules,
+            root: root.to_owned(),
+            prefix,
+        })
+    }
+
+    /// Return the owners of the file at `relative_path`, relative to the rendered directory.
+    ///
+    /// The last matching rule wins, and files without a matching rule or with a rule without owners are unowned,
+    /// just like files outside of the repository, which may be listed by their absolute path.
+    pub fn owners_of(&self, relative_path: &Path) -> &[String] {
+        let path = self.prefix.join(relative_path);
+        let path = if path.has_root() {
+            match path.strip_prefix(&self.root) {
+                Ok(path) => path,
+                Err(_) => return &[],
+            }
+        } else {
+            &path
+        };
+        self.rules
+            .iter()
+            .rev()
+            .find(|(pattern, _)| pattern.matched_path_or_any_parents(path, false).is_ignore())
+            .map_or(&[], |(_, owners)| owners.as_slice())
+    }
+}
+
+/// Split `line` at whitespace that isn't escaped with a backslash.
+fn split_unescaped_whitespace(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+    while let Some(chr) = chars.next() {
+        match chr {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    if !escaped.is_whitespace() {
+                        fields.last_mut().expect("non-empty").push('\\');
+                    }
+                    fields.last_mut().expect("non-empty").push(escaped);
+                }
+            }
+            chr if chr.is_whitespace() => {
+                if !fields.last().expect("non-empty").is_empty() {
+                    fields.push(String::new());
+                }
+            }
+            chr => fields.last_mut().expect("non-empty").push(chr),
+        }
+    }
+    if fields.len() > 1 && fields.last().expect("non-empty").is_empty() {
+        fields.pop();
+    }
+    fields
+}
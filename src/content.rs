@@ -0,0 +1,185 @@
+//! Files to render, whose content is only loaded while it is needed.
+use crate::encoding;
+use anyhow::Context;
+use encoding_rs::Encoding;
+use std::borrow::Cow;
+use std::io::Read;
+use std::path::PathBuf;
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+/// Where the content of a file comes from.
+#[derive(Debug, Clone)]
+enum Source {
+    /// The first `size` bytes of the file are read and decoded with `encoding` after skipping `offset` bytes of
+    /// byte order mark.
+    ///
+    /// The `first_line`, which may identify the syntax, is kept so it doesn't have to be read again to find it.
+    Disk {
+        encoding: &'static Encoding,
+        offset: usize,
+        size: u64,
+        first_line: String,
+    },
+    /// The first `size` bytes of the file are binary and read as hex dump, see [`encoding::hex_dump()`].
+    Hex { size: u64 },
+    /// The content is kept in memory, as it was altered or doesn't come from a file on disk.
+    Memory(String),
+}
+
+/// A file to render.
+#[derive(Debug, Clone)]
+pub struct File {
+    pub path: PathBuf,
+    /// The index of the root in [`DirContents::roots`](crate::DirContents::roots) the file was read from.
+    pub root: usize,
+    /// The amount of lines of the content, as determined when the file was discovered.
+    pub lines: usize,
+    /// The size of the content in bytes, as determined when the file was discovered.
+    pub len: usize,
+    /// The name or extension of the syntax to highlight the file with, instead of detecting it.
+    pub language: Option<String>,
+    source: Source,
+}
+
+impl File {
+    /// Create a file from `content` which is kept in memory.
+    pub fn in_memory(path: PathBuf, content: String) -> Self {
+        File {
+            path,
+            root: 0,
+            language: None,
+            lines: content.lines().count(),
+            len: content.len(),
+            source: Source::Memory(content),
+        }
+    }
+
+    /// Create a file whose `content` was decoded from the `size` bytes of the file at `path` with `encoding`,
+    /// after skipping `offset` bytes of byte order mark, and which will be read again when needed.
+    pub(crate) fn on_disk(
+        path: PathBuf,
+        content: &str,
+        size: u64,
+        encoding: &'static Encoding,
+        offset: usize,
+    ) -> Self {
+        File {
+            path,
+            root: 0,
+            language: None,
+            lines: content.lines().count(),
+            len: content.len(),
+            source: Source::Disk {
+                encoding,
+                offset,
+                size,
+                first_line: first_line(content).to_owned(),
+            },
+        }
+    }
+
+    /// Create a binary file at `path` of `size` bytes whose hex dump is `content`, and which will be read again
+    /// when needed.
+    pub(crate) fn hex_on_disk(path: PathBuf, content: &str, size: u64) -> Self {
+        File {
+            path,
+            root: 0,
+            language: None,
+            lines: content.lines().count(),
+            len: content.len(),
+            source: Source::Hex { size },
+        }
+    }
+
+    /// Load the content of the file, by reading and decoding it if it is on disk.
+    ///
+    /// The content always has as many lines as the file had when it was discovered, even if it changed since,
+    /// as the layout of the image depends on it.
+    pub fn content(&self) -> anyhow::Result<Cow<'_, str>> {
+        let content = match &self.source {
+            Source::Memory(content) => return Ok(Cow::Borrowed(content)),
+            Source::Disk {
+                encoding,
+                offset,
+                size,
+                ..
+            } => {
+                let bytes = self.read(*size)?;
+                let bytes = bytes.get(*offset..).unwrap_or_default();
+                encoding.decode_without_bom_handling(bytes).0.into_owned()
+            }
+            Source::Hex { size } => encoding::hex_dump(&self.read(*size)?),
+        };
+        Ok(Cow::Owned(with_line_count(content, self.lines)))
+    }
+
+    /// Find the syntax of the file by its `language` if set, by its name or extension, or by its first line,
+    /// like a shebang.
+    ///
+    /// Unlike [`SyntaxSet::find_syntax_for_file()`], this works for files which aren't on disk as well.
+    pub fn syntax<'a>(&self, ss: &'a SyntaxSet) -> anyhow::Result<Option<&'a SyntaxReference>> {
+        if let Some(language) = &self.language {
+            return Ok(ss.find_syntax_by_token(language));
+        }
+        let by_name = |name: Option<&std::ffi::OsStr>| {
+            name.and_then(|name| name.to_str())
+                .and_then(|name| ss.find_syntax_by_extension(name))
+        };
+        if let Some(syntax) =
+            by_name(self.path.file_name()).or_else(|| by_name(self.path.extension()))
+        {
+            return Ok(Some(syntax));
+        }
+        let first_line = match &self.source {
+            Source::Memory(content) => first_line(content),
+            Source::Disk { first_line, .. } => first_line,
+            Source::Hex { .. } => return Ok(None),
+        };
+        Ok(ss.find_syntax_by_first_line(first_line))
+    }
+
+    /// Read at most `size` bytes, the size of the file when it was discovered, so files which grew since
+    /// don't take more memory than accounted for.
+    fn read(&self, size: u64) -> anyhow::Result<Vec<u8>> {
+        let file = std::fs::File::open(&self.path)
+            .with_context(|| format!("Failed to open {:?}", self.path))?;
+        let mut bytes = Vec::with_capacity(usize::try_from(size).unwrap_or_default());
+        file.take(size)
+            .read_to_end(&mut bytes)
+            .with_context(|| format!("Failed to read {:?}", self.path))?;
+        Ok(bytes)
+    }
+}
+
+/// Return the first line of `content`, or its beginning if the line is very long, which is enough to find its syntax
+/// by shebangs, mode lines or XML declarations.
+fn first_line(content: &str) -> &str {
+    const MAX_LEN: usize = 1024;
+    let line = content.lines().next().unwrap_or_default();
+    let end = (0..=MAX_LEN.min(line.len()))
+        .rev()
+        .find(|end| line.is_char_boundary(*end))
+        .unwrap_or_default();
+    &line[..end]
+}
+
+/// Cut `content` after `lines` lines, or add empty lines until it has that many.
+fn with_line_count(mut content: String, lines: usize) -> String {
+    let actual_lines = content.lines().count();
+    if actual_lines > lines {
+        let end = match lines.checked_sub(1) {
+            Some(last) => content
+                .match_indices('\n')
+                .nth(last)
+                .map_or(content.len(), |(index, _)| index + 1),
+            None => 0,
+        };
+        content.truncate(end);
+    } else if actual_lines < lines {
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.extend(std::iter::repeat_n('\n', lines - actual_lines));
+    }
+    content
+}
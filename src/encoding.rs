@@ -0,0 +1,157 @@
+//! Decode text files in other encodings than UTF-8, like Windows-1252, UTF-16 or Shift-JIS.
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+/// The amount of bytes at the start of a file which are inspected to recognize UTF-16 without byte order mark.
+const UTF16_SNIFF_LEN: usize = 1024;
+
+/// Files smaller than this many bytes are too short to tell UTF-16 without byte order mark from binary data.
+const UTF16_MIN_LEN: usize = 16;
+
+/// The amount of bytes at the start of a file which are inspected to recognize binary files.
+const BINARY_SNIFF_LEN: usize = 8192;
+
+/// Files with more than this percentage of control characters are considered binary.
+const BINARY_MAX_CONTROL_PERCENT: usize = 10;
+
+/// The amount of bytes shown in each line of a hex dump.
+const HEX_BYTES_PER_LINE: usize = 32;
+
+/// The text of a file along with the encoding it was decoded from.
+pub struct Decoded {
+    pub content: String,
+    pub encoding: &'static Encoding,
+    /// The length of the byte order mark in front of the encoded text.
+    pub offset: usize,
+    /// True if some bytes were invalid in `encoding` and replaced with `char::REPLACEMENT_CHARACTER`.
+    pub had_errors: bool,
+}
+
+/// Decode `bytes` of a file by detecting their encoding, or return them as error if they look binary.
+///
+/// Byte order marks take precedence, followed by UTF-16 without byte order mark. Otherwise, files with NUL bytes
+/// or many control characters are considered binary, even if they are valid UTF-8, and the encoding of all others
+/// is guessed unless they are valid UTF-8.
+/// Bytes which are invalid in the detected encoding are replaced with `char::REPLACEMENT_CHARACTER`.
+pub fn decode(bytes: Vec<u8>) -> Result<Decoded, Vec<u8>> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(&bytes) {
+        return Ok(Decoded {
+            offset: bom_len,
+            ..decode_with(encoding, &bytes[bom_len..])
+        });
+    }
+    // UTF-16 comes first as its NUL bytes would make it look binary.
+    if let Some(encoding) = sniff_utf16(&bytes) {
+        return Ok(decode_with(encoding, &bytes));
+    }
+    if is_binary(&bytes) {
+        return Err(bytes);
+    }
+    let bytes = match String::from_utf8(bytes) {
+        Ok(content) => {
+            return Ok(Decoded {
+                content,
+                encoding: UTF_8,
+                offset: 0,
+                had_errors: false,
+            })
+        }
+        Err(err) => err.into_bytes(),
+    };
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(&bytes, true);
+    Ok(decode_with(detector.guess(None, true), &bytes))
+}
+
+/// Shorten `bytes`, which were cut off after an arbitrary amount of bytes, to the end of their last line,
+/// or of their last character if there is no line end, so a cut off character can't make UTF-8 look like
+/// another encoding.
+///
+/// UTF-16 is left as is, as the byte order mark or its NUL bytes identify it either way.
+pub fn truncate_to_line(bytes: &mut Vec<u8>) {
+    if Encoding::for_bom(bytes).is_some_and(|(encoding, _)| encoding != UTF_8)
+        || sniff_utf16(bytes).is_some()
+    {
+        return;
+    }
+    let end = match bytes.iter().rposition(|byte| *byte == b'\n') {
+        Some(index) => index + 1,
+        // a UTF-8 character is at most 4 bytes long, so a cut off one starts within the last 3 bytes.
+        None => match (bytes.len().saturating_sub(3)..bytes.len())
+            .rev()
+            .find(|index| bytes[*index] >= 0b1100_0000)
+        {
+            Some(start) if start + utf8_len(bytes[start]) > bytes.len() => start,
+            _ => return,
+        },
+    };
+    bytes.truncate(end);
+}
+
+/// Return the length of the UTF-8 character starting with the `lead` byte.
+fn utf8_len(lead: u8) -> usize {
+    match lead {
+        0b1111_0000.. => 4,
+        0b1110_0000.. => 3,
+        _ => 2,
+    }
+}
+
+/// Return true if `bytes` contain NUL bytes or too many control characters other than whitespace and escapes.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(BINARY_SNIFF_LEN)];
+    let controls = sample
+        .iter()
+        .filter(|byte| matches!(byte, 0..=8 | 14..=26 | 28..=31 | 127))
+        .count();
+    sample.contains(&0) || controls * 100 > sample.len() * BINARY_MAX_CONTROL_PERCENT
+}
+
+/// Turn `bytes` into lines of lowercase hex digits without separators.
+pub fn hex_dump(bytes: &[u8]) -> String {
+    let mut dump = String::with_capacity(bytes.len() * 2 + bytes.len() / HEX_BYTES_PER_LINE + 1);
+    for line in bytes.chunks(HEX_BYTES_PER_LINE) {
+        for nibble in line.iter().flat_map(|byte| [byte >> 4, byte & 0xf]) {
+            dump.push(char::from_digit(nibble as u32, 16).expect("nibbles are hex digits"));
+        }
+        dump.push('\n');
+    }
+    dump
+}
+
+fn decode_with(encoding: &'static Encoding, bytes: &[u8]) -> Decoded {
+    let (content, had_errors) = encoding.decode_without_bom_handling(bytes);
+    Decoded {
+        content: content.into_owned(),
+        encoding,
+        offset: 0,
+        had_errors,
+    }
+}
+
+/// Recognize UTF-16 without byte order mark by its NUL bytes, which are the high bytes of ASCII characters
+/// and thus at all odd positions for little endian, or all even positions for big endian.
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(UTF16_SNIFF_LEN) & !1];
+    if sample.len() < UTF16_MIN_LEN {
+        return None;
+    }
+    let pairs = sample.len() / 2;
+    let nuls_at = |parity: usize| {
+        sample
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|byte| **byte == 0)
+            .count()
+    };
+    let (even, odd) = (nuls_at(0), nuls_at(1));
+    let mostly = |count: usize| count * 10 >= pairs * 4;
+    let hardly = |count: usize| count * 20 <= pairs;
+    if mostly(odd) && hardly(even) {
+        Some(UTF_16LE)
+    } else if mostly(even) && hardly(odd) {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,113 @@
+//! Heuristics to recognize files which weren't written by hand, similar to those of GitHub's linguist.
+use std::path::{Component, Path};
+
+/// How files that weren't written by hand are treated.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Treatment {
+    /// Render them like all other files.
+    #[default]
+    Keep,
+    /// Don't render them at all.
+    Skip,
+    /// Render them dimmed.
+    Dim,
+}
+
+/// Why a file is considered not to be written by hand.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+    /// The file is in a directory of third-party code, like `vendor/` or `node_modules/`.
+    Vendored,
+    /// The file pins the versions of dependencies, like `Cargo.lock`.
+    Lockfile,
+    /// The file says it was generated in one of its first lines.
+    Generated,
+    /// The file is minified, either by name like `app.min.js` or by having extremely long lines.
+    Minified,
+}
+
+/// Directories containing third-party code.
+const VENDOR_DIRS: &[&str] = &[
+    "vendor",
+    "vendored",
+    "third_party",
+    "third-party",
+    "thirdparty",
+    "node_modules",
+    "bower_components",
+    "Godeps",
+    "Pods",
+];
+
+/// Files written by package managers.
+const LOCKFILES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "Gemfile.lock",
+    "Pipfile.lock",
+    "poetry.lock",
+    "uv.lock",
+    "composer.lock",
+    "go.sum",
+    "flake.lock",
+    "mix.lock",
+    "Podfile.lock",
+    "packages.lock.json",
+    "gradle.lockfile",
+];
+
+/// The amount of lines at the start of a file which are searched for `GENERATED_MARKERS`.
+const HEADER_LINES: usize = 5;
+
+/// Comments marking a file as generated, compared case-insensitively.
+const GENERATED_MARKERS: &[&str] = &[
+    "@generated",
+    "do not edit",
+    "code generated by",
+    "auto-generated",
+    "autogenerated",
+];
+
+/// Files with an average line length above this many bytes are considered minified.
+const MINIFIED_AVERAGE_LINE_LENGTH: usize = 200;
+
+/// Files smaller than this many bytes are never considered minified due to their line length.
+const MINIFIED_MIN_SIZE: usize = 2048;
+
+/// Determine if the file at `relative_path`, relative to the input directory, with `content` wasn't written by hand.
+pub fn classify(relative_path: &Path, content: &str) -> Option<Kind> {
+    let file_name = relative_path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    if relative_path
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .any(|component| match component {
+            Component::Normal(dir) => VENDOR_DIRS.iter().any(|vendor| dir == *vendor),
+            _ => false,
+        })
+    {
+        Some(Kind::Vendored)
+    } else if LOCKFILES.contains(&file_name.as_ref()) {
+        Some(Kind::Lockfile)
+    } else if content.lines().take(HEADER_LINES).any(|line| {
+        let line = line.to_lowercase();
+        GENERATED_MARKERS.iter().any(|marker| line.contains(marker))
+    }) {
+        Some(Kind::Generated)
+    } else if file_name.contains(".min.") || is_minified(content) {
+        Some(Kind::Minified)
+    } else {
+        None
+    }
+}
+
+fn is_minified(content: &str) -> bool {
+    let lines = content.lines().count().max(1);
+    content.len() >= MINIFIED_MIN_SIZE && content.len() / lines > MINIFIED_AVERAGE_LINE_LENGTH
+}
@@ -1,29 +1,462 @@
-use anyhow::bail;
+use anyhow::{bail, Context};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use prodash::Progress;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
 use std::ffi::OsString;
+use std::io::Read;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
+pub mod archive;
+pub mod attributes;
+pub mod codeowners;
+pub mod content;
+pub mod encoding;
+pub mod generated;
+pub mod overlay;
 pub mod render;
+pub mod sort;
 pub use render::function::render;
 
 // The number of lines used for displaying filenames at
 // the top of files.
 const FILENAME_LINE_COUNT: u32 = 1;
 
+/// The line appended to files which were truncated as they exceeded a limit.
+pub const TRUNCATION_MARKER: &str = "\u{22ef} truncated \u{22ef}";
+
+/// A directory whose files are rendered.
+#[derive(Debug, Clone)]
+pub struct Root {
+    pub dir: PathBuf,
+    /// The name of the directory, which labels its files if there are multiple roots.
+    pub name: String,
+}
+
+impl Root {
+    /// Create a root for `dir`, named after the last component of its canonical path.
+    pub fn new(dir: PathBuf) -> Self {
+        let canonical = dir.canonicalize().ok();
+        let name = canonical
+            .as_deref()
+            .unwrap_or(&dir)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| dir.display().to_string());
+        Root { dir, name }
+    }
+}
+
+#[derive(Default)]
 pub struct DirContents {
-    pub parent_dir: PathBuf,
-    pub children_content: Vec<(PathBuf, String)>,
+    /// The directories the files were read from, in the order they were given.
+    pub roots: Vec<Root>,
+    pub children_content: Vec<content::File>,
+    /// The paths of files in `children_content` which weren't written by hand, if these are to be dimmed.
+    pub generated: HashSet<PathBuf>,
+    /// The paths of files in `children_content` which are binary and whose content is a hex dump.
+    pub binary: HashSet<PathBuf>,
+    /// The paths of files in `children_content` which were truncated and end with the [`TRUNCATION_MARKER`] line.
+    pub truncated: HashSet<PathBuf>,
+}
+
+impl DirContents {
+    /// Return the path of `file` relative to the root it was read from.
+    pub fn relative_path<'a>(&self, file: &'a content::File) -> &'a Path {
+        file.path
+            .strip_prefix(&self.roots[file.root].dir)
+            .unwrap_or(&file.path)
+    }
+
+    /// Return the path `file` is shown and matched by, which is its [relative path](Self::relative_path())
+    /// prefixed with the name of its root if there are multiple roots.
+    pub fn label<'a>(&self, file: &'a content::File) -> Cow<'a, Path> {
+        let relative_path = self.relative_path(file);
+        if self.roots.len() > 1 {
+            Cow::Owned(Path::new(&self.roots[file.root].name).join(relative_path))
+        } else {
+            Cow::Borrowed(relative_path)
+        }
+    }
+
+    /// Add the roots and files of `other` after those of `self`.
+    ///
+    /// Roots with the same name as a previous one get a number appended to keep their labels apart.
+    pub fn append(&mut self, other: DirContents) {
+        let offset = self.roots.len();
+        for mut root in other.roots {
+            let name = root.name.clone();
+            let mut count = 1;
+            while self.roots.iter().any(|previous| previous.name == root.name) {
+                count += 1;
+                root.name = format!("{name}-{count}");
+            }
+            self.roots.push(root);
+        }
+        self.children_content
+            .extend(other.children_content.into_iter().map(|mut file| {
+                file.root += offset;
+                file
+            }));
+        self.generated.extend(other.generated);
+        self.binary.extend(other.binary);
+        self.truncated.extend(other.truncated);
+    }
 }
 
+/// Which files to read from the input directory.
+///
+/// All filters apply together, so a file is read only if it passes every one of them.
+#[derive(Debug, Default, Clone)]
+pub struct Filter {
+    /// Extensions of files to skip, like `md` for markdown files.
+    pub ignore_extensions: Vec<OsString>,
+    /// If not empty, only files with one of these extensions are read.
+    pub whitelist_extensions: Vec<OsString>,
+    /// If not empty, only files matching these gitignore-style globs, relative to the input directory, are read.
+    /// Globs starting with `!` exclude files matched by previous globs again.
+    pub include: Vec<String>,
+    /// Files and directories matching these gitignore-style globs, relative to the input directory, are skipped.
+    /// Globs starting with `!` include files matched by previous globs again.
+    pub exclude: Vec<String>,
+    /// What to do with files that weren't written by hand, as determined by [`generated::classify()`]
+    /// or declared with the `linguist-generated` and `linguist-vendored` attributes in `.gitattributes`.
+    pub generated: generated::Treatment,
+    /// If true, binary files are read as a hex dump, see [`encoding::hex_dump()`], instead of being skipped.
+    pub hex_binaries: bool,
+    /// Files larger than this many bytes are skipped, which is determined before reading them.
+    pub max_file_size: Option<u64>,
+    /// Files with more lines than this are skipped.
+    pub max_file_lines: Option<usize>,
+    /// If true, files exceeding `max_file_size` or `max_file_lines` are truncated to the limit instead of being
+    /// skipped, with a [`TRUNCATION_MARKER`] line appended to them.
+    pub truncate: bool,
+    /// If true, hidden files and directories whose name starts with a dot are read as well,
+    /// except for `.git` directories.
+    pub hidden: bool,
+    /// If true, symbolic links are followed, while links forming a loop are skipped. Otherwise, all links are skipped.
+    pub follow_symlinks: bool,
+    /// Only descend this many directories into the input directory, where `0` is the input directory itself.
+    pub max_depth: Option<usize>,
+    /// If true, no ignore files are respected, neither those of git nor `.ignore` and `.codevisignore` files.
+    pub no_ignore: bool,
+    /// If true, the ignore files of git aren't respected, like `.gitignore`, `.git/info/exclude`
+    /// and the global gitignore file, while `.ignore` and `.codevisignore` files still are.
+    pub no_ignore_vcs: bool,
+    /// The number of threads to discover and read files with, where `0` uses one thread per logical core.
+    pub threads: usize,
+}
+
+/// The amount of files which were skipped while reading the input directory, by reason.
+#[derive(Debug, Default, Clone)]
+pub struct Skipped {
+    /// Files with an ignored extension, or without a whitelisted one.
+    pub extension: usize,
+    /// Files which weren't written by hand, see [`Filter::generated`].
+    pub generated: usize,
+    /// Files declared as `binary` in `.gitattributes`, or which look binary, see [`encoding::is_binary()`].
+    pub binary: usize,
+    /// Files which couldn't be read.
+    pub unreadable: usize,
+    /// Files exceeding the maximum size or amount of lines.
+    pub too_large: usize,
+    /// Symbolic links pointing to one of the directories containing them, see [`Filter::follow_symlinks`].
+    pub symlink_loops: usize,
+}
+
+impl std::ops::AddAssign for Skipped {
+    fn add_assign(&mut self, other: Skipped) {
+        self.extension += other.extension;
+        self.generated += other.generated;
+        self.binary += other.binary;
+        self.unreadable += other.unreadable;
+        self.too_large += other.too_large;
+        self.symlink_loops += other.symlink_loops;
+    }
+}
+
+impl Filter {
+    /// Return true if the file at `path` should be skipped due to its extension.
+    fn is_ignored_extension(&self, path: &Path) -> bool {
+        let extension = path.extension();
+        let matches = |extensions: &[OsString]| {
+            extension.is_some_and(|ext| extensions.iter().any(|e| e == ext))
+        };
+        (!self.whitelist_extensions.is_empty() && !matches(&self.whitelist_extensions))
+            || matches(&self.ignore_extensions)
+    }
+
+    /// The amount of bytes to read at most from each file.
+    pub(crate) fn max_size(&self) -> u64 {
+        self.max_file_size.unwrap_or(u64::MAX)
+    }
+
+    /// Return the file at `path` counted as skipped if it is skipped due to its extension.
+    pub(crate) fn skips_extension(&self, path: &Path) -> Option<Skipped> {
+        self.is_ignored_extension(path).then(|| Skipped {
+            extension: 1,
+            ..Default::default()
+        })
+    }
+
+    /// Return a file with the `declared` attributes and `size` in bytes counted as skipped if it is skipped
+    /// without reading it.
+    pub(crate) fn skips_before_reading(
+        &self,
+        declared: &attributes::Declared,
+        size: u64,
+    ) -> Option<Skipped> {
+        if declared.binary && !self.hex_binaries {
+            Some(Skipped {
+                binary: 1,
+                ..Default::default()
+            })
+        } else if size > self.max_size() && !self.truncate {
+            Some(Skipped {
+                too_large: 1,
+                ..Default::default()
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The number of threads to discover and read files with, which is at least one.
+    fn threads(&self) -> usize {
+        (self.threads == 0)
+            .then(num_cpus::get)
+            .unwrap_or(self.threads)
+    }
+}
+
+/// Build a gitignore-style matcher for `globs`, which are relative to `root`.
+fn glob_matcher(root: &Path, globs: &[String]) -> anyhow::Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(root);
+    for glob in globs {
+        builder
+            .add_line(None, glob)
+            .with_context(|| format!("Invalid glob {glob:?}"))?;
+    }
+    Ok(builder.build()?)
+}
+
+/// Return true if the file at `path`, which is within the root of both matchers, isn't matched by `exclude`,
+/// and matched by `include` unless it's empty.
+fn is_selected(include: &Gitignore, exclude: &Gitignore, path: &Path) -> bool {
+    !exclude.matched_path_or_any_parents(path, false).is_ignore()
+        && (include.is_empty() || include.matched_path_or_any_parents(path, false).is_ignore())
+}
+
+/// Read all text files in `search_path`, decoded from their detected encoding, which pass the `filter`, and respect `.gitignore`, `.codevisignore`
+/// and `.gitattributes` files unless the `filter` says otherwise.
+///
+/// If `search_path` is a file, only this file is read, with its parent directory as root.
+/// Returns the contents along with the amount of files which were skipped.
 pub fn unicode_content(
     search_path: &Path,
-    ignore_extensions: &[OsString],
+    filter: &Filter,
     mut progress: impl Progress,
     should_interrupt: &AtomicBool,
-) -> anyhow::Result<(DirContents, usize)> {
+) -> anyhow::Result<(DirContents, Skipped)> {
+    if search_path.is_file() {
+        let parent = search_path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let name = search_path.file_name().context("Files have a name")?;
+        return unicode_content_of_files(
+            parent,
+            &[name.into()],
+            filter,
+            progress,
+            should_interrupt,
+        );
+    }
+    let include = glob_matcher(search_path, &filter.include)?;
+    let exclude = glob_matcher(search_path, &filter.exclude)?;
+    let root = search_path.to_owned();
+    let mut walk = ignore::WalkBuilder::new(search_path);
+    walk.hidden(!filter.hidden)
+        .follow_links(filter.follow_symlinks)
+        .max_depth(filter.max_depth)
+        .ignore(!filter.no_ignore)
+        .git_ignore(!filter.no_ignore && !filter.no_ignore_vcs)
+        .git_global(!filter.no_ignore && !filter.no_ignore_vcs)
+        .git_exclude(!filter.no_ignore && !filter.no_ignore_vcs);
+    if !filter.no_ignore {
+        walk.add_custom_ignore_filename(".codevisignore");
+    }
+    let (tx, rx) = flume::unbounded();
+    walk.filter_entry(move |entry| {
+        if !entry
+            .path()
+            .strip_prefix(&root)
+            .is_ok_and(|path| !path.as_os_str().is_empty())
+        {
+            return true;
+        }
+        // the matchers strip their root themselves, and would strip it twice from paths relative to it.
+        let path = entry.path();
+        let is_dir = entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_dir());
+        if exclude.matched(path, is_dir).is_ignore() || (is_dir && entry.file_name() == ".git") {
+            return false;
+        }
+        // directories are always entered, as files within them may still be included.
+        is_dir || include.is_empty() || include.matched_path_or_any_parents(path, false).is_ignore()
+    })
+    .threads(filter.threads())
+    .build_parallel()
+    .run(|| {
+        let tx = tx.clone();
+        Box::new(move |entry| {
+            if should_interrupt.load(Ordering::Relaxed) {
+                return ignore::WalkState::Quit;
+            }
+            // only regular files are read, as reading other kinds of files like FIFOs could block forever.
+            // Symbolic links only have the type of their target if they are followed, and are skipped otherwise.
+            let entry = match entry {
+                Ok(entry)
+                    if !entry
+                        .file_type()
+                        .is_some_and(|file_type| file_type.is_file()) =>
+                {
+                    return ignore::WalkState::Continue;
+                }
+                entry => entry,
+            };
+            if tx.send(entry.map(ignore::DirEntry::into_path)).is_err() {
+                return ignore::WalkState::Quit;
+            }
+            ignore::WalkState::Continue
+        })
+    });
+    drop(tx);
+    if should_interrupt.load(Ordering::Relaxed) {
+        bail!("Cancelled by user")
+    }
+
+    // the parallel walk finds files in any order, so sort them to read them in a deterministic order.
+    let mut paths = Vec::new();
+    let mut symlink_loops = Vec::new();
+    for entry in rx {
+        match entry {
+            Ok(path) => paths.push(path),
+            Err(err) => match symlink_loop(&err) {
+                Some((ancestor, child)) => {
+                    symlink_loops.push((child.to_owned(), ancestor.to_owned()));
+                }
+                None => return Err(err.into()),
+            },
+        }
+    }
+    paths.sort();
+    symlink_loops.sort();
+    for (child, ancestor) in &symlink_loops {
+        progress.info(format!(
+            "Skipped symbolic link {child:?} as it points to {ancestor:?} containing it"
+        ));
+    }
+    let (contents, mut skipped) =
+        read_files(search_path, &paths, filter, progress, should_interrupt)?;
+    skipped.symlink_loops = symlink_loops.len();
+    Ok((contents, skipped))
+}
+
+/// Read the text files at `paths`, which are relative to `search_path` unless they are absolute, like
+/// [`unicode_content()`] does, but in the given order and without walking `search_path`.
+///
+/// Thus `.gitignore` and `.codevisignore` files don't apply, while `.gitattributes` files and the `filter` still do.
+pub fn unicode_content_of_files(
+    search_path: &Path,
+    paths: &[PathBuf],
+    filter: &Filter,
+    progress: impl Progress,
+    should_interrupt: &AtomicBool,
+) -> anyhow::Result<(DirContents, Skipped)> {
+    let include = glob_matcher(search_path, &filter.include)?;
+    let exclude = glob_matcher(search_path, &filter.exclude)?;
+    let paths: Vec<_> = paths
+        .iter()
+        .map(|path| search_path.join(path))
+        .filter(|path| !path.starts_with(search_path) || is_selected(&include, &exclude, path))
+        .collect();
+    read_files(search_path, &paths, filter, progress, should_interrupt)
+}
+
+/// Read all of `input`, like stdin, as the content of a single file called `name`, which passes the `filter`
+/// unless it is skipped.
+///
+/// As `name` usually has no extension, set [the language](content::File::language) to highlight it properly.
+pub fn unicode_content_of_reader(
+    name: &str,
+    input: impl Read,
+    filter: &Filter,
+    mut progress: impl Progress,
+) -> anyhow::Result<(DirContents, Skipped)> {
+    let mut reader = Reader::new(Root {
+        dir: PathBuf::new(),
+        name: name.to_owned(),
+    });
+    // the size of the input isn't known up front, but one byte more than the maximum size tells if it exceeds it.
+    let mut bytes = Vec::new();
+    input
+        .take(filter.max_size().saturating_add(1))
+        .read_to_end(&mut bytes)
+        .with_context(|| format!("Failed to read {name}"))?;
+    let size = bytes.len() as u64;
+    match filter.skips_before_reading(&Default::default(), size) {
+        Some(skipped) => reader.add(Outcome::skipped(skipped)),
+        None => {
+            bytes.truncate(filter.max_size().try_into().unwrap_or(usize::MAX));
+            reader.add(Outcome::decode(
+                filter,
+                name.into(),
+                Path::new(name),
+                Default::default(),
+                size,
+                bytes,
+                false,
+            ));
+        }
+    }
+    Ok(reader.finish(&mut progress))
+}
+
+/// Parse a list of paths, one per line or separated by NUL bytes like the output of `git ls-files -z`,
+/// ignoring empty entries.
+pub fn parse_file_list(list: &[u8]) -> anyhow::Result<Vec<PathBuf>> {
+    use bstr::ByteSlice;
+    let separator = if list.contains(&0) { b'\0' } else { b'\n' };
+    list.split(|byte| *byte == separator)
+        .map(|path| {
+            if separator == b'\n' {
+                path.strip_suffix(b"\r").unwrap_or(path)
+            } else {
+                path
+            }
+        })
+        .filter(|path| !path.is_empty())
+        .map(|path| {
+            path.to_path()
+                .map(ToOwned::to_owned)
+                .with_context(|| format!("Invalid path {:?}", path.as_bstr()))
+        })
+        .collect()
+}
+
+/// Read the files at `paths`, which are files in `search_path` and its subdirectories or elsewhere.
+fn read_files(
+    search_path: &Path,
+    paths: &[PathBuf],
+    filter: &Filter,
+    mut progress: impl Progress,
+    should_interrupt: &AtomicBool,
+) -> anyhow::Result<(DirContents, Skipped)> {
     let start = std::time::Instant::now();
-    progress.init(None, Some(prodash::unit::label("files")));
+    progress.init(Some(paths.len()), Some(prodash::unit::label("files")));
     let mut content_progress = progress.add_child("content");
     content_progress.init(
         None,
@@ -33,36 +466,323 @@ pub fn unicode_content(
         )),
     );
 
-    let mut paths = Vec::new();
-    let mut ignored = 0;
-    for entry in ignore::Walk::new(search_path) {
-        if should_interrupt.load(Ordering::Relaxed) {
-            bail!("Cancelled by user")
-        }
-        progress.inc();
-        let entry = entry?;
-        let path = entry.path();
-        if !ignore_extensions.is_empty()
-            && path.extension().map_or(false, |ext| {
-                ignore_extensions.iter().any(|extension| ext == extension)
-            })
-        {
-            ignored += 1;
-            continue;
+    let attributes = attributes::Attributes::new(search_path)?;
+    let next_path = AtomicUsize::default();
+    let (tx, rx) = flume::unbounded();
+    // files are read in any order, and added in the order of `paths` once all are read.
+    let outcomes = std::thread::scope(|scope| -> anyhow::Result<Vec<Option<Outcome>>> {
+        for _ in 0..filter.threads().min(paths.len()) {
+            let tx = tx.clone();
+            let next_path = &next_path;
+            let attributes = &attributes;
+            scope.spawn(move || loop {
+                let index = next_path.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(index) else {
+                    break;
+                };
+                if should_interrupt.load(Ordering::Relaxed) {
+                    break;
+                }
+                let read = read_file(filter, attributes, search_path, path.clone());
+                if tx
+                    .send(read.map(|(outcome, bytes)| (index, outcome, bytes)))
+                    .is_err()
+                {
+                    break;
+                }
+            });
         }
-        if let Ok(content) = std::fs::read_to_string(path) {
-            content_progress.inc_by(content.len());
-            paths.push((path.to_owned(), content));
+        drop(tx);
+
+        let mut outcomes: Vec<_> = paths.iter().map(|_| None).collect();
+        for result in rx {
+            if should_interrupt.load(Ordering::Relaxed) {
+                bail!("Cancelled by user")
+            }
+            let (index, outcome, bytes) = result?;
+            outcomes[index] = Some(outcome);
+            content_progress.inc_by(bytes);
+            progress.inc();
         }
+        Ok(outcomes)
+    })?;
+    if should_interrupt.load(Ordering::Relaxed) {
+        bail!("Cancelled by user")
+    }
+    for warning in attributes.take_warnings() {
+        progress.fail(warning);
     }
 
+    let mut reader = Reader::new(Root::new(search_path.to_path_buf()));
+    for outcome in outcomes.into_iter().flatten() {
+        reader.add(outcome);
+    }
+    let contents = reader.finish(&mut progress);
     progress.show_throughput(start);
     content_progress.show_throughput(start);
-    Ok((
-        DirContents {
-            parent_dir: search_path.to_path_buf(),
-            children_content: paths,
-        },
-        ignored,
-    ))
+    Ok(contents)
+}
+
+/// Read the file at `path` in `search_path` according to the `filter`, returning what became of it along with
+/// the amount of bytes read from it.
+fn read_file(
+    filter: &Filter,
+    attributes: &attributes::Attributes,
+    search_path: &Path,
+    path: PathBuf,
+) -> anyhow::Result<(Outcome, usize)> {
+    if let Some(skipped) = filter.skips_extension(&path) {
+        return Ok((Outcome::skipped(skipped), 0));
+    }
+    let relative_path = path.strip_prefix(search_path).unwrap_or(&path).to_owned();
+    let declared = attributes.of(&relative_path);
+    let unreadable = || {
+        Outcome::skipped(Skipped {
+            unreadable: 1,
+            ..Default::default()
+        })
+    };
+    // listed files may be of any kind, but only regular files can be read without blocking.
+    let Some(size) = std::fs::metadata(&path)
+        .ok()
+        .filter(std::fs::Metadata::is_file)
+        .map(|metadata| metadata.len())
+    else {
+        return Ok((unreadable(), 0));
+    };
+    if let Some(skipped) = filter.skips_before_reading(&declared, size) {
+        return Ok((Outcome::skipped(skipped), 0));
+    }
+    let mut bytes = Vec::new();
+    if std::fs::File::open(&path)
+        .and_then(|file| file.take(filter.max_size()).read_to_end(&mut bytes))
+        .is_err()
+    {
+        return Ok((unreadable(), 0));
+    }
+    let len = bytes.len();
+    let outcome = Outcome::decode(filter, path, &relative_path, declared, size, bytes, true);
+    Ok((outcome, len))
+}
+
+/// Return the ancestor directory and the symbolic link pointing to it if `err` is due to a loop of symbolic links.
+fn symlink_loop(err: &ignore::Error) -> Option<(&Path, &Path)> {
+    match err {
+        ignore::Error::Loop { ancestor, child } => Some((ancestor, child)),
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => symlink_loop(err),
+        _ => None,
+    }
+}
+
+/// Adds the files which were read and counts the files which were skipped, in the order they are added.
+pub(crate) struct Reader {
+    contents: DirContents,
+    skipped: Skipped,
+    /// The amount of files by the name of the encoding they were decoded from, if it isn't UTF-8.
+    decoded_from: BTreeMap<&'static str, usize>,
+    /// The amount of files with bytes that were invalid in their encoding.
+    with_errors: usize,
+}
+
+/// A file which was read, or the reason it was skipped for, to be added to a [`Reader`].
+#[derive(Default)]
+pub(crate) struct Outcome {
+    /// The file, unless it was skipped.
+    file: Option<content::File>,
+    /// The file counted by the reason it was skipped for, if it was skipped.
+    skipped: Skipped,
+    /// If true, the file is one of [`DirContents::generated`].
+    generated: bool,
+    /// If true, the file is one of [`DirContents::binary`].
+    binary: bool,
+    /// If true, the file is one of [`DirContents::truncated`].
+    truncated: bool,
+    /// The name of the encoding the file was decoded from, if it isn't UTF-8.
+    decoded_from: Option<&'static str>,
+    /// If true, the file had bytes that were invalid in its encoding.
+    with_errors: bool,
+}
+
+impl Outcome {
+    /// A file which was skipped, as counted by `skipped`.
+    pub(crate) fn skipped(skipped: Skipped) -> Self {
+        Outcome {
+            skipped,
+            ..Default::default()
+        }
+    }
+
+    /// Turn the file at `path` and `relative_path` within its root, which has the `declared` attributes and `size`,
+    /// and whose first bytes up to the [maximum size](Filter::max_size()) are `bytes`, into its content according
+    /// to the `filter`.
+    ///
+    /// Files `on_disk` are read again when rendering them unless their content was altered, while the content
+    /// of all others is kept in memory.
+    pub(crate) fn decode(
+        filter: &Filter,
+        path: PathBuf,
+        relative_path: &Path,
+        declared: attributes::Declared,
+        size: u64,
+        mut bytes: Vec<u8>,
+        on_disk: bool,
+    ) -> Self {
+        let mut is_truncated = size > filter.max_size();
+        let decoded = if declared.binary {
+            Err(bytes)
+        } else {
+            // the partial last line is dropped anyway, but a character cut in half would garble the whole file.
+            if is_truncated && !encoding::is_binary(&bytes) {
+                encoding::truncate_to_line(&mut bytes);
+            }
+            encoding::decode(bytes)
+        };
+        let decoded = match decoded {
+            Ok(decoded) => decoded,
+            Err(bytes) if filter.hex_binaries => {
+                let mut content = encoding::hex_dump(&bytes);
+                let file = if is_truncated {
+                    content.push_str(TRUNCATION_MARKER);
+                    content.push('\n');
+                    content::File::in_memory(path, content)
+                } else if on_disk {
+                    content::File::hex_on_disk(path, &content, size)
+                } else {
+                    content::File::in_memory(path, content)
+                };
+                return Outcome {
+                    file: Some(file),
+                    binary: true,
+                    truncated: is_truncated,
+                    ..Default::default()
+                };
+            }
+            Err(_) => {
+                return Outcome::skipped(Skipped {
+                    binary: 1,
+                    ..Default::default()
+                });
+            }
+        };
+        let mut content = decoded.content;
+        if is_truncated {
+            // drop the last line as it was probably cut off.
+            content.truncate(content.rfind('\n').map_or(0, |index| index + 1));
+        }
+        if let Some(max_lines) = filter.max_file_lines {
+            // the end of the last line within the limit, if there are more lines.
+            let end = match max_lines.checked_sub(1) {
+                Some(last) => content
+                    .match_indices('\n')
+                    .nth(last)
+                    .map(|(index, _)| index + 1),
+                None => Some(0),
+            }
+            .filter(|end| *end < content.len());
+            if let Some(end) = end {
+                if !filter.truncate {
+                    return Outcome::skipped(Skipped {
+                        too_large: 1,
+                        ..Default::default()
+                    });
+                }
+                content.truncate(end);
+                is_truncated = true;
+            }
+        }
+        let mut generated = false;
+        if filter.generated != generated::Treatment::Keep
+            && declared
+                .is_generated()
+                .unwrap_or_else(|| generated::classify(relative_path, &content).is_some())
+        {
+            if filter.generated == generated::Treatment::Skip {
+                return Outcome::skipped(Skipped {
+                    generated: 1,
+                    ..Default::default()
+                });
+            }
+            generated = true;
+        }
+        // only altered content is kept in memory, all other files on disk are read again when rendering them.
+        let file = if is_truncated {
+            content.push_str(TRUNCATION_MARKER);
+            content.push('\n');
+            content::File::in_memory(path, content)
+        } else if on_disk {
+            content::File::on_disk(path, &content, size, decoded.encoding, decoded.offset)
+        } else {
+            content::File::in_memory(path, content)
+        };
+        Outcome {
+            file: Some(file),
+            generated,
+            truncated: is_truncated,
+            decoded_from: (decoded.encoding != encoding_rs::UTF_8).then(|| decoded.encoding.name()),
+            with_errors: decoded.had_errors,
+            ..Default::default()
+        }
+    }
+}
+
+impl Reader {
+    /// Create a reader for the files of `root`.
+    pub(crate) fn new(root: Root) -> Self {
+        Reader {
+            contents: DirContents {
+                roots: vec![root],
+                ..Default::default()
+            },
+            skipped: Skipped::default(),
+            decoded_from: BTreeMap::new(),
+            with_errors: 0,
+        }
+    }
+
+    /// Add the file of `outcome` after all previously added files, or count it if it was skipped.
+    pub(crate) fn add(&mut self, outcome: Outcome) {
+        self.skipped += outcome.skipped;
+        let Some(file) = outcome.file else {
+            return;
+        };
+        if outcome.generated {
+            self.contents.generated.insert(file.path.clone());
+        }
+        if outcome.binary {
+            self.contents.binary.insert(file.path.clone());
+        }
+        if outcome.truncated {
+            self.contents.truncated.insert(file.path.clone());
+        }
+        if let Some(encoding) = outcome.decoded_from {
+            *self.decoded_from.entry(encoding).or_default() += 1;
+        }
+        if outcome.with_errors {
+            self.with_errors += 1;
+        }
+        self.contents.children_content.push(file);
+    }
+
+    /// Return the contents of all added files along with the amount of skipped files, after logging
+    /// how files were altered to `progress`.
+    pub(crate) fn finish(self, progress: &mut impl Progress) -> (DirContents, Skipped) {
+        for (encoding, count) in self.decoded_from {
+            progress.info(format!("Decoded {count} files from {encoding}"));
+        }
+        if !self.contents.truncated.is_empty() {
+            progress.info(format!(
+                "Truncated {} files exceeding the size or line limit",
+                self.contents.truncated.len()
+            ));
+        }
+        if self.with_errors != 0 {
+            progress.info(format!(
+                "Replaced invalid bytes in {} files with the replacement character",
+                self.with_errors
+            ));
+        }
+        (self.contents, self.skipped)
+    }
 }
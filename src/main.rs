@@ -2,6 +2,7 @@ use anyhow::Context;
 use image::{ImageBuffer, Rgb};
 use memmap2::MmapMut;
 use std::borrow::Cow;
+use std::io::Read;
 use std::path::Path;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
@@ -12,6 +13,19 @@ mod options;
 
 fn main() -> anyhow::Result<()> {
     let mut args: options::Args = clap::Parser::parse();
+    // stdin can only be read once, and would be empty for all but the first reader.
+    let stdin_readers = args
+        .input
+        .iter()
+        .chain(&args.files_from)
+        .chain(&args.diagnostics)
+        .filter(|path| *path == Path::new("-"))
+        .count();
+    if stdin_readers > 1 {
+        anyhow::bail!(
+            "Only one of --input, --files-from and --diagnostics can be `-` to read from stdin"
+        )
+    }
 
     let should_interrupt = Arc::new(AtomicBool::new(false));
     let _ = signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&should_interrupt));
@@ -42,43 +56,124 @@ fn main() -> anyhow::Result<()> {
     );
 
     // determine files to render
-    let (mut dir_contents, mut ignored) = codevis::unicode_content(
-        &args.input_dir,
-        &args.ignore_extension,
-        progress.add_child("search unicode files"),
-        &should_interrupt,
-    )
-    .with_context(|| {
-        format!(
-            "Failed to find input files in {:?} directory",
-            args.input_dir
-        )
-    })?;
-
-    // filter extensions if there is a whitelist
-    if !args.whitelist_extension.is_empty() {
-        let mut whitelist_ignored: usize = 0;
-        dir_contents.children_content.retain(|(path, _)| {
-            path.extension().map_or(false, |ext| {
-                if args.whitelist_extension.contains(&ext.to_owned()) {
-                    true
-                } else {
-                    whitelist_ignored += 1;
-                    false
-                }
-            })
-        });
-        ignored = whitelist_ignored;
-    }
-
-    dir_contents
-        .children_content
-        .sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
-
-    // log num ignored files
-    if ignored != 0 {
-        progress.add_child("input").info(format!(
-            "Ignored {ignored} files that matched ignored extensions"
+    let filter = codevis::Filter {
+        ignore_extensions: args.ignore_extension.clone(),
+        whitelist_extensions: args.whitelist_extension.clone(),
+        include: args.include.clone(),
+        exclude: args.exclude.clone(),
+        generated: args.generated,
+        hex_binaries: args.hex_binaries,
+        max_file_size: args.max_file_size.map(|size| size.as_u64()),
+        max_file_lines: args.max_file_lines,
+        truncate: args.truncate,
+        hidden: args.hidden,
+        follow_symlinks: args.follow_symlinks,
+        max_depth: args.max_depth,
+        no_ignore: args.no_ignore,
+        no_ignore_vcs: args.no_ignore_vcs,
+        threads: args.threads,
+    };
+    let mut dir_contents = codevis::DirContents::default();
+    let mut skipped = codevis::Skipped::default();
+    if let Some(files_from) = &args.files_from {
+        let input_dir = match args.input.as_slice() {
+            [] => Path::new("."),
+            [input_dir] if input_dir.is_dir() => input_dir,
+            _ => anyhow::bail!("Files can only be read from a list for a single input directory"),
+        };
+        let list = if files_from == Path::new("-") {
+            let mut list = Vec::new();
+            std::io::stdin().read_to_end(&mut list)?;
+            list
+        } else {
+            std::fs::read(files_from)
+                .with_context(|| format!("Failed to read the list of files at {files_from:?}"))?
+        };
+        (dir_contents, skipped) = codevis::unicode_content_of_files(
+            input_dir,
+            &codevis::parse_file_list(&list)?,
+            &filter,
+            progress.add_child("read listed files"),
+            &should_interrupt,
+        )?;
+    } else {
+        for input in &args.input {
+            let (root_contents, root_skipped) = if input == Path::new("-") {
+                codevis::unicode_content_of_reader(
+                    "stdin",
+                    std::io::stdin().lock(),
+                    &filter,
+                    progress.add_child("read stdin"),
+                )?
+            } else if input.is_file() && codevis::archive::Kind::of(input).is_some() {
+                codevis::archive::unicode_content(
+                    input,
+                    &filter,
+                    progress.add_child("read archive"),
+                    &should_interrupt,
+                )?
+            } else {
+                codevis::unicode_content(
+                    input,
+                    &filter,
+                    progress.add_child("search unicode files"),
+                    &should_interrupt,
+                )
+                .with_context(|| format!("Failed to find input files in {input:?}"))?
+            };
+            dir_contents.append(root_contents);
+            skipped += root_skipped;
+        }
+    }
+
+    let ss = SyntaxSet::load_defaults_newlines();
+    if let Some(language) = &args.language {
+        if ss.find_syntax_by_token(language).is_none() {
+            anyhow::bail!("Unknown language {language:?}, it must be the name or an extension of a syntax, like `rust` or `rs`")
+        }
+        for file in &mut dir_contents.children_content {
+            file.language = Some(language.clone());
+        }
+    }
+    let order = args.sort.unwrap_or(match args.files_from {
+        Some(_) => codevis::sort::Order::Given,
+        None => codevis::sort::Order::Path,
+    });
+    codevis::sort::sort(&mut dir_contents, order, &ss, args.sort_list.as_deref())?;
+
+    // log num skipped files
+    let mut input_progress = progress.add_child("input");
+    if skipped.extension != 0 {
+        input_progress.info(format!(
+            "Ignored {} files due to their extension",
+            skipped.extension
+        ));
+    }
+    if skipped.binary != 0 {
+        input_progress.info(format!("Skipped {} binary files", skipped.binary));
+    }
+    if skipped.too_large != 0 {
+        input_progress.info(format!(
+            "Skipped {} files exceeding the size or line limit",
+            skipped.too_large
+        ));
+    }
+    if skipped.unreadable != 0 {
+        input_progress.info(format!(
+            "Skipped {} files which couldn't be read",
+            skipped.unreadable
+        ));
+    }
+    if skipped.generated != 0 {
+        input_progress.info(format!(
+            "Skipped {} generated, vendored or minified files",
+            skipped.generated
+        ));
+    }
+    if skipped.symlink_loops != 0 {
+        input_progress.info(format!(
+            "Skipped {} symbolic links forming a loop",
+            skipped.symlink_loops
         ));
     }
 
@@ -88,7 +183,91 @@ fn main() -> anyhow::Result<()> {
         args.theme = ts.themes.keys().map(ToOwned::to_owned).collect();
     }
 
-    let ss = SyntaxSet::load_defaults_newlines();
+    let repo_name = dir_contents
+        .roots
+        .iter()
+        .map(|root| root.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let date = codevis::render::caption::today();
+
+    let highlight_patterns: Vec<_> = args
+        .highlight_pattern
+        .iter()
+        .enumerate()
+        .map(|(index, regex)| codevis::render::search::Pattern {
+            regex: regex.clone(),
+            color: args
+                .highlight_color
+                .get(index)
+                .copied()
+                .unwrap_or_else(|| codevis::render::color::palette(index)),
+        })
+        .collect();
+
+    let focus = match &args.focus_list {
+        Some(list) => Some(codevis::render::focus::Focus::from_list(
+            list,
+            &args.focus,
+            args.focus_dim,
+        )?),
+        None if !args.focus.is_empty() => Some(codevis::render::focus::Focus::new(
+            &args.focus,
+            Vec::new(),
+            args.focus_dim,
+        )?),
+        None => None,
+    };
+
+    let code_owners = dir_contents
+        .roots
+        .iter()
+        .map(|root| match &args.codeowners {
+            Some(path) => {
+                codevis::codeowners::CodeOwners::from_path(path, &root.dir, Default::default())
+                    .map(Some)
+            }
+            None if args.bg_pixel_color == codevis::render::BgColor::Owner => {
+                codevis::codeowners::CodeOwners::discover(&root.dir)
+            }
+            None => Ok(None),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut line_overlays = Vec::new();
+    if let Some(report) = &args.coverage {
+        line_overlays.push(codevis::overlay::coverage::load(report)?);
+    }
+    if let Some(diagnostics) = &args.diagnostics {
+        line_overlays.push(codevis::overlay::diagnostics::load(diagnostics)?);
+    }
+    if let Some(profile) = &args.profile {
+        line_overlays.push(codevis::overlay::profile::load(
+            profile,
+            args.profile_scale,
+        )?);
+    }
+    for annotations in &args.annotations {
+        line_overlays.push(codevis::overlay::annotations::load(
+            annotations,
+            if args.annotation_gradient.is_empty() {
+                &codevis::render::color::HEAT_GRADIENT
+            } else {
+                &args.annotation_gradient
+            },
+            args.annotation_scale,
+        )?);
+    }
+    if args.blame {
+        line_overlays.push(codevis::overlay::blame::load(
+            &dir_contents,
+            args.blame_authors,
+            args.threads,
+            progress.add_child("git blame"),
+            &should_interrupt,
+        )?);
+    }
+
     for theme in &args.theme {
         let start = std::time::Instant::now();
 
@@ -120,6 +299,28 @@ fn main() -> anyhow::Result<()> {
                 ignore_files_without_syntax: args.ignore_files_without_syntax,
                 tab_spaces: args.tab_spaces,
                 line_nums: args.line_nums,
+                depth_gradient: if args.depth_gradient.is_empty() {
+                    &codevis::render::color::HEAT_GRADIENT
+                } else {
+                    &args.depth_gradient
+                },
+                gradient_max_depth: args.gradient_max_depth,
+                caption: args.caption.map(|position| codevis::render::Caption {
+                    position,
+                    title: args.caption_title.as_deref(),
+                    repo_name: &repo_name,
+                    date: &date,
+                    scale: args.caption_scale,
+                }),
+                highlight_patterns: &highlight_patterns,
+                highlight_whole_lines: args.highlight_whole_lines,
+                focus: focus.as_ref(),
+                line_overlays: &line_overlays,
+                code_owners: &code_owners,
+                replacement_color: args.replacement_color,
+                dim_generated: (args.generated == codevis::generated::Treatment::Dim)
+                    .then_some(args.focus_dim),
+                separate_roots: args.separate_roots,
             },
         )?;
         let img_path = if args.theme.len() == 1 {
@@ -4,9 +4,38 @@ use std::path::PathBuf;
 #[derive(Debug, clap::Parser)]
 #[clap(version)]
 pub struct Args {
-    /// The directory to read UTF-8 encoded text files from.
-    #[clap(long, short = 'i', help_heading = "INPUT")]
-    pub input_dir: PathBuf,
+    /// The directory to read text files from, a single file, or a `.tar`, `.tar.gz`, `.tgz` or `.zip` archive
+    /// to read them from without extracting it. Use `-` to read text from stdin, which may need `--language`.
+    ///
+    /// Can be given multiple times to render several directories, like multiple repositories, into one image.
+    /// Their files are then labeled with the name of their directory, like `backend/src/main.rs`.
+    #[clap(
+        long,
+        short = 'i',
+        visible_alias = "input-dir",
+        required_unless_present = "files_from",
+        help_heading = "INPUT"
+    )]
+    pub input: Vec<PathBuf>,
+
+    /// The language to highlight all files with instead of detecting it from their name, which is needed
+    /// for text from stdin. Either the name of a syntax or one of its extensions, like `rust` or `rs`.
+    #[clap(long, help_heading = "INPUT")]
+    pub language: Option<String>,
+
+    /// A file with the paths of files to render, relative to the input directory, instead of searching it.
+    /// Use `-` to read the paths from stdin.
+    ///
+    /// Paths are separated by newlines or NUL bytes, like the output of `git ls-files -z` or `fd -0`.
+    /// Files are placed in the order of the list unless `--sort` is given, and ignore files don't apply to them.
+    /// The input directory defaults to the current directory.
+    #[clap(long, help_heading = "INPUT")]
+    pub files_from: Option<PathBuf>,
+
+    /// Start the files of each input directory in a new column, so each directory occupies its own region of the image.
+    /// Without `--show-filenames`, each region starts with a line showing the name of its directory.
+    #[clap(long, help_heading = "INPUT")]
+    pub separate_roots: bool,
 
     /// An extension to ignore, like `md` for markdown files.
     /// You can add multiple extensions by seperating them with commas like so `--ignore_extension rs,lock`.
@@ -15,19 +44,86 @@ pub struct Args {
 
     /// An extension to render, like `md` for markdown files. All other extensions will be ignored.
     /// You can add multiple extensions by seperating them with commas like so `--whitelist_extension rs,lock`.
-    #[clap(
-        long,
-        conflicts_with("ignore_extension"),
-        help_heading = "INPUT",
-        value_delimiter = ','
-    )]
+    #[clap(long, help_heading = "INPUT", value_delimiter = ',')]
     pub whitelist_extension: Vec<OsString>,
 
+    /// A gitignore-style glob, relative to the input directory, of files to render, like `src/**`.
+    /// All other files will be ignored. Can be given multiple times, and globs starting with `!` exclude
+    /// files matched by previous globs again.
+    #[clap(long, help_heading = "INPUT")]
+    pub include: Vec<String>,
+
+    /// A gitignore-style glob, relative to the input directory, of files or directories to ignore, like `**/generated/`.
+    /// Can be given multiple times, and globs starting with `!` include files matched by previous globs again.
+    #[clap(long, help_heading = "INPUT")]
+    pub exclude: Vec<String>,
+
+    /// The order in which files are placed in the image.
+    ///
+    /// Defaults to `path`, or to `given` when reading files with `--files-from`.
+    #[clap(value_enum, long, help_heading = "INPUT")]
+    pub sort: Option<codevis::sort::Order>,
+
+    /// A file with one path per line, relative to the input directory, to place files in when sorting by `list`.
+    /// With multiple input directories, paths start with the name of their directory.
+    #[clap(long, required_if_eq("sort", "list"), help_heading = "INPUT")]
+    pub sort_list: Option<PathBuf>,
+
+    /// How to treat files which weren't written by hand, like vendored dependencies, lockfiles,
+    /// files marked as generated in their first lines and minified files.
+    ///
+    /// Files can be declared as such, or not, with the `linguist-generated` and `linguist-vendored`
+    /// attributes in `.gitattributes` files, which take precedence over the built-in heuristics.
+    #[clap(value_enum, long, default_value_t = codevis::generated::Treatment::Keep, help_heading = "INPUT")]
+    pub generated: codevis::generated::Treatment,
+
+    /// If true, binary files are rendered as strips of hex digits, with brighter digits for higher values,
+    /// instead of being skipped.
+    #[clap(long, help_heading = "INPUT")]
+    pub hex_binaries: bool,
+
+    /// Skip files larger than this, like `10MB` or `512KiB`, which is checked before reading them.
+    #[clap(long, help_heading = "INPUT")]
+    pub max_file_size: Option<bytesize::ByteSize>,
+
+    /// Skip files with more lines than this.
+    #[clap(long, help_heading = "INPUT")]
+    pub max_file_lines: Option<usize>,
+
+    /// If true, files exceeding `--max-file-size` or `--max-file-lines` are rendered up to the limit
+    /// and end with a highlighted marker line, instead of being skipped.
+    #[clap(long, help_heading = "INPUT")]
+    pub truncate: bool,
+
+    /// If true, hidden files and directories whose name starts with a dot are rendered as well,
+    /// except for `.git` directories.
+    #[clap(long, help_heading = "INPUT")]
+    pub hidden: bool,
+
+    /// If true, symbolic links are followed, like those to shared folders. Links which point to a directory
+    /// containing them are reported and skipped. Without this flag, symbolic links are skipped.
+    #[clap(long, help_heading = "INPUT")]
+    pub follow_symlinks: bool,
+
+    /// Only descend this many directories into the input directory, where `1` only renders the files
+    /// directly within it.
+    #[clap(long, help_heading = "INPUT")]
+    pub max_depth: Option<usize>,
+
+    /// If true, no ignore files are respected, neither `.gitignore` nor `.ignore` and `.codevisignore` files.
+    #[clap(long, help_heading = "INPUT")]
+    pub no_ignore: bool,
+
+    /// If true, the ignore files of git aren't respected, like `.gitignore`, `.git/info/exclude`
+    /// and the global gitignore file, while `.ignore` and `.codevisignore` files still are.
+    #[clap(long, help_heading = "INPUT")]
+    pub no_ignore_vcs: bool,
+
     /// If true, files that would be rendered white due to lack of syntax are skipped.
     #[clap(long, help_heading = "INPUT")]
     pub ignore_files_without_syntax: bool,
 
-    /// The number of threads to use for rendering.
+    /// The number of threads to use for finding and reading input files, running `git blame` and rendering.
     ///
     /// '0' is equivalent to using all logical cores, this is also the default.
     #[clap(long, short = 't', default_value_t = num_cpus::get(), help_heading = "PERFORMANCE")]
@@ -109,10 +205,121 @@ pub struct Args {
     #[clap(value_enum, long, default_value_t = codevis::render::BgColor::Style, help_heading = "COLORS")]
     pub bg_pixel_color: codevis::render::BgColor,
 
+    /// The hex colors of the gradient used by the `indentation-depth` and `bracket-depth` foreground colors,
+    /// from shallow to deep. Defaults to green, yellow and red.
+    /// You can add multiple colors by seperating them with commas like so `--depth-gradient 0000ff,#ff0000`.
+    #[clap(long, value_parser = codevis::render::color::parse, value_delimiter = ',', help_heading = "COLORS")]
+    pub depth_gradient: Vec<image::Rgb<u8>>,
+
+    /// The nesting depth at which the last color of the `--depth-gradient` is reached.
+    #[clap(long, default_value_t = 8, help_heading = "COLORS")]
+    pub gradient_max_depth: u32,
+
+    /// The CODEOWNERS file used by the `owner` background color, with patterns relative to each input directory.
+    ///
+    /// By default, the standard locations in each input directory and all of its parents are searched.
+    #[clap(long, help_heading = "COLORS")]
+    pub codeowners: Option<PathBuf>,
+
     /// The difference in brightness that certain background color styles may have at most.
     #[clap(long, default_value_t = 0.3, help_heading = "COLORS")]
     pub color_modulation: f32,
 
+    /// A regular expression whose matches are tinted on top of the syntax colors, like `unsafe` or `TODO`.
+    ///
+    /// Can be given multiple times to highlight multiple patterns, earlier patterns take precedence.
+    #[clap(long, help_heading = "COLORS")]
+    pub highlight_pattern: Vec<regex::Regex>,
+
+    /// The hex colors to tint the matches of each `--highlight-pattern` with, in the same order.
+    /// Patterns without a color are assigned one from a built-in palette.
+    /// You can add multiple colors by seperating them with commas like so `--highlight-color ff0000,#00ff00`.
+    #[clap(long, value_parser = codevis::render::color::parse, value_delimiter = ',', requires("highlight_pattern"), help_heading = "COLORS")]
+    pub highlight_color: Vec<image::Rgb<u8>>,
+
+    /// Tint the entire line containing a match of a `--highlight-pattern` instead of only the matched text.
+    #[clap(long, requires("highlight_pattern"), help_heading = "COLORS")]
+    pub highlight_whole_lines: bool,
+
+    /// A glob matching files to keep at full brightness, like `src/render/**`. All other files are dimmed.
+    ///
+    /// Globs are matched against paths relative to the input directory. With several `--input`s, these paths are
+    /// prefixed with the name of their input directory, like `backend/src/render/**`. Can be given multiple times.
+    #[clap(long, help_heading = "COLORS")]
+    pub focus: Vec<String>,
+
+    /// A file with one path per line to keep at full brightness, in addition to `--focus` globs.
+    /// Paths are relative to the input directory, and prefixed with its name with several `--input`s, like `--focus`.
+    /// Directories include all files within them.
+    #[clap(long, help_heading = "COLORS")]
+    pub focus_list: Option<PathBuf>,
+
+    /// A hex color to paint characters with that replace bytes which were invalid in the detected encoding of a file,
+    /// to make them stand out.
+    #[clap(long, value_parser = codevis::render::color::parse, help_heading = "COLORS")]
+    pub replacement_color: Option<image::Rgb<u8>>,
+
+    /// How strongly files out of focus, or generated files with `--generated dim`, are dimmed,
+    /// from 0.0 (not at all) to 1.0 (invisible).
+    #[clap(long, default_value_t = 0.75, help_heading = "COLORS")]
+    pub focus_dim: f32,
+
+    /// A coverage report to tint covered, partially covered and uncovered lines with.
+    ///
+    /// Supports lcov tracefiles, Cobertura XML and the JSON of `llvm-cov export`.
+    /// Paths in the report may be absolute or relative to the input directory or the root of its git repository.
+    #[clap(long, help_heading = "OVERLAYS")]
+    pub coverage: Option<PathBuf>,
+
+    /// Compiler or linter diagnostics to tint lines with, colored by their most severe diagnostic.
+    ///
+    /// Supports SARIF and the output of `cargo check --message-format=json`. Use `-` to read from stdin.
+    #[clap(long, help_heading = "OVERLAYS")]
+    pub diagnostics: Option<PathBuf>,
+
+    /// Collapsed stacks, as used by flamegraphs, to color lines with by the amount of samples they were on the stack for.
+    ///
+    /// Frames need a `file:line` location like `render (src/render/function.rs:42)` to be mapped onto lines.
+    #[clap(long, help_heading = "OVERLAYS")]
+    pub profile: Option<PathBuf>,
+
+    /// How sample counts of the `--profile` are mapped onto colors.
+    #[clap(value_enum, long, default_value_t = codevis::overlay::Scale::Log, requires("profile"), help_heading = "OVERLAYS")]
+    pub profile_scale: codevis::overlay::Scale,
+
+    /// A CSV or JSON lines file with arbitrary data to paint onto ranges of lines. Can be given multiple times.
+    ///
+    /// CSV files have the columns `path,line_start,line_end,color_or_value[,label]`, where colors are written
+    /// like `#ff8800` and numbers are mapped onto the `--annotation-gradient`.
+    /// JSON lines files have one object per line with the fields `path`, `line_start`, `line_end`, `color` or `value`
+    /// and an optional `label`.
+    #[clap(long, help_heading = "OVERLAYS")]
+    pub annotations: Vec<PathBuf>,
+
+    /// The hex colors of the gradient that numeric `--annotations` are mapped onto, from the smallest to the largest value.
+    /// Defaults to green, yellow and red.
+    #[clap(long, value_parser = codevis::render::color::parse, value_delimiter = ',', requires("annotations"), help_heading = "OVERLAYS")]
+    pub annotation_gradient: Vec<image::Rgb<u8>>,
+
+    /// How numeric `--annotations` are mapped onto the `--annotation-gradient`.
+    #[clap(value_enum, long, default_value_t = codevis::overlay::Scale::Linear, requires("annotations"), help_heading = "OVERLAYS")]
+    pub annotation_scale: codevis::overlay::Scale,
+
+    /// Color lines by the author who last changed them, according to `git blame`.
+    ///
+    /// Files which aren't tracked by git are left untouched.
+    #[clap(long, help_heading = "OVERLAYS")]
+    pub blame: bool,
+
+    /// The amount of authors with the most lines who get their own color with `--blame`, while all others share one.
+    #[clap(
+        long,
+        default_value_t = 8,
+        requires("blame"),
+        help_heading = "OVERLAYS"
+    )]
+    pub blame_authors: usize,
+
     /// Open the output image with the standard image viewer.
     #[clap(long, help_heading = "OUTPUT")]
     pub open: bool,
@@ -129,4 +336,24 @@ pub struct Args {
     /// The number of spaces that a tab character is equivalent to.
     #[clap(long, default_value_t = 4, help_heading = "IMAGE")]
     pub tab_spaces: u32,
+
+    /// Add a band with a title, the repository name, the render date, the theme, line and file totals
+    /// and a color legend above or below the rendered code.
+    #[clap(value_enum, long, help_heading = "CAPTION")]
+    pub caption: Option<codevis::render::caption::Position>,
+
+    /// The title to write into the caption. Defaults to the name of the input directory.
+    #[clap(long, requires("caption"), help_heading = "CAPTION")]
+    pub caption_title: Option<String>,
+
+    /// The factor by which the caption text is scaled up.
+    ///
+    /// '0' picks a scale based on the width of the image, this is also the default.
+    #[clap(
+        long,
+        default_value_t = 0,
+        requires("caption"),
+        help_heading = "CAPTION"
+    )]
+    pub caption_scale: u32,
 }
@@ -0,0 +1,153 @@
+//! Read arbitrary per-line data, like ownership or test flakiness, from CSV or JSON lines files.
+use crate::overlay::{LineOverlay, Scale};
+use crate::render::color;
+use anyhow::{bail, Context};
+use image::Rgb;
+use std::path::{Path, PathBuf};
+
+/// What to paint onto a range of lines.
+enum Paint {
+    /// A color to use as is, along with its description in the legend.
+    Color(Rgb<u8>, Option<String>),
+    /// A number to map onto a gradient.
+    Value(f64),
+}
+
+struct Annotation {
+    path: PathBuf,
+    first: u32,
+    last: u32,
+    paint: Paint,
+}
+
+/// Load annotations from the file at `path` and turn them into an overlay.
+///
+/// Each annotation covers the 1-based lines `line_start` to `line_end`, inclusive, of a file and
+/// either has a hex `color` or a numeric `value` which is mapped onto `gradient` using `scale`.
+/// CSV files have the columns `path,line_start,line_end,color_or_value[,label]`, where a first line
+/// with a non-numeric `line_start` is a header and lines starting with `#` are comments.
+/// JSON lines files have one object per line with the fields `path`, `line_start`, `line_end`,
+/// `color` or `value` and an optional `label`.
+///
+/// Colors take precedence over values if a line has both, and labels describe colors in the legend.
+pub fn load(path: &Path, gradient: &[Rgb<u8>], scale: Scale) -> anyhow::Result<LineOverlay> {
+    let input = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read annotations at {path:?}"))?;
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext == "jsonl" || ext == "json")
+        || input.trim_start().starts_with('{');
+    let annotations = if is_json {
+        parse_json_lines(&input)
+    } else {
+        parse_csv(&input)
+    }
+    .with_context(|| format!("Failed to parse annotations at {path:?}"))?;
+
+    let mut values = Vec::new();
+    let mut colors = Vec::new();
+    for annotation in &annotations {
+        match &annotation.paint {
+            Paint::Value(value) => values.push((
+                annotation.path.as_path(),
+                annotation.first,
+                annotation.last,
+                *value,
+            )),
+            Paint::Color(color, label) => colors.push((
+                &annotation.path,
+                annotation.first,
+                annotation.last,
+                *color,
+                label,
+            )),
+        }
+    }
+
+    let mut overlay = if values.is_empty() {
+        LineOverlay::default()
+    } else {
+        LineOverlay::from_weighted_ranges(&values, gradient, scale, None, |value| {
+            format!("{value:.2}")
+                .trim_end_matches('0')
+                .trim_end_matches('.')
+                .to_owned()
+        })
+    };
+    for (path, first, last, color, label) in colors {
+        overlay.set_range(path, first, last, color);
+        if !overlay.legend.iter().any(|(c, _)| *c == color) {
+            let label = label
+                .clone()
+                .unwrap_or_else(|| format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2]));
+            overlay.legend.push((color, label));
+        }
+    }
+    Ok(overlay)
+}
+
+fn parse_paint(color_or_value: &str, label: Option<String>) -> anyhow::Result<Paint> {
+    let color_or_value = color_or_value.trim();
+    Ok(match color_or_value.parse::<f64>() {
+        Ok(value) if !color_or_value.starts_with('#') => Paint::Value(value),
+        _ => Paint::Color(color::parse(color_or_value)?, label),
+    })
+}
+
+fn parse_csv(input: &str) -> anyhow::Result<Vec<Annotation>> {
+    let mut annotations = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<_> = line.splitn(5, ',').map(str::trim).collect();
+        let [path, first, last, color_or_value, rest @ ..] = fields.as_slice() else {
+            bail!("Expected at least 4 columns in line {}", index + 1)
+        };
+        let first = match first.parse::<u32>() {
+            Ok(first) => first,
+            Err(_) if index == 0 => continue,
+            Err(err) => bail!("Invalid line_start in line {}: {err}", index + 1),
+        };
+        annotations.push(Annotation {
+            path: PathBuf::from(path),
+            first,
+            last: last
+                .parse()
+                .with_context(|| format!("Invalid line_end in line {}", index + 1))?,
+            paint: parse_paint(color_or_value, rest.first().map(|label| label.to_string()))
+                .with_context(|| format!("Invalid color or value in line {}", index + 1))?,
+        });
+    }
+    Ok(annotations)
+}
+
+fn parse_json_lines(input: &str) -> anyhow::Result<Vec<Annotation>> {
+    let mut annotations = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let context = || format!("Invalid annotation in line {}", index + 1);
+        let object: serde_json::Value = serde_json::from_str(line).with_context(context)?;
+        let path = object["path"].as_str().with_context(context)?;
+        let first = object["line_start"].as_u64().with_context(context)? as u32;
+        let last = object["line_end"]
+            .as_u64()
+            .map_or(first, |last| last as u32);
+        let label = object["label"].as_str().map(ToOwned::to_owned);
+        let paint = match (object["color"].as_str(), object["value"].as_f64()) {
+            (Some(color), _) => Paint::Color(color::parse(color).with_context(context)?, label),
+            (None, Some(value)) => Paint::Value(value),
+            (None, None) => bail!("Expected 'color' or 'value' in line {}", index + 1),
+        };
+        annotations.push(Annotation {
+            path: PathBuf::from(path),
+            first,
+            last,
+            paint,
+        });
+    }
+    Ok(annotations)
+}
@@ -0,0 +1,162 @@
+//! Color lines by the author who last changed them, according to `git blame`.
+use crate::overlay::LineOverlay;
+use crate::render::color;
+use crate::DirContents;
+use anyhow::{bail, Context};
+use image::Rgb;
+use prodash::Progress;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// The color of lines by authors who aren't among the top authors.
+pub const OTHER: Rgb<u8> = Rgb([128, 128, 128]);
+
+/// The authors of each line of a file, where the first entry is the author of the first line.
+type Authors = Vec<String>;
+
+/// Run `git blame` on all files of `dir_contents` and color their lines by the author who last changed them.
+///
+/// The `top_authors` with the most lines get their own color, while all other authors share a gray
+/// color. Files that aren't tracked by git are left untouched, and uncommitted changes are attributed
+/// to the author `Not Committed Yet`, as `git blame` does.
+///
+/// `git blame` runs on `threads` files at once, where `0` uses one thread per logical core.
+pub fn load(
+    dir_contents: &DirContents,
+    top_authors: usize,
+    threads: usize,
+    mut progress: impl Progress,
+    should_interrupt: &AtomicBool,
+) -> anyhow::Result<LineOverlay> {
+    let files = &dir_contents.children_content;
+    progress.init(Some(files.len()), prodash::unit::label("files").into());
+
+    // lines are stored by absolute path, which is how overlays find the files of each root.
+    let roots: Vec<_> = dir_contents
+        .roots
+        .iter()
+        .map(|root| root.dir.canonicalize().unwrap_or_else(|_| root.dir.clone()))
+        .collect();
+    let next_file = AtomicUsize::default();
+    let (tx, rx) = flume::unbounded();
+    let blames = std::thread::scope(|scope| -> anyhow::Result<Vec<(PathBuf, Authors)>> {
+        let threads = (threads == 0).then(num_cpus::get).unwrap_or(threads);
+        for _ in 0..threads.min(files.len()) {
+            let tx = tx.clone();
+            let next_file = &next_file;
+            let roots = &roots;
+            scope.spawn(move || {
+                while let Some(file) = files.get(next_file.fetch_add(1, Ordering::Relaxed)) {
+                    if should_interrupt.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let authors = blame(
+                        &dir_contents.roots[file.root].dir,
+                        dir_contents.relative_path(file),
+                    );
+                    let path = roots[file.root].join(dir_contents.relative_path(file));
+                    if tx.send(authors.map(|a| a.map(|a| (path, a)))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        let mut blames = Vec::new();
+        for result in rx {
+            if should_interrupt.load(Ordering::Relaxed) {
+                bail!("Cancelled by user")
+            }
+            if let Some(blame) = result? {
+                blames.push(blame);
+            }
+            progress.inc();
+        }
+        Ok(blames)
+    })?;
+    if should_interrupt.load(Ordering::Relaxed) {
+        bail!("Cancelled by user")
+    }
+    if blames.is_empty() {
+        bail!(
+            "Did not find a single file tracked by git in {:?}",
+            dir_contents
+                .roots
+                .iter()
+                .map(|root| &root.dir)
+                .collect::<Vec<_>>()
+        )
+    }
+
+    let mut line_counts = HashMap::<&str, usize>::new();
+    for author in blames.iter().flat_map(|(_, authors)| authors) {
+        *line_counts.entry(author).or_default() += 1;
+    }
+    let mut line_counts: Vec<_> = line_counts.into_iter().collect();
+    line_counts.sort_by(|(a, a_lines), (b, b_lines)| b_lines.cmp(a_lines).then(a.cmp(b)));
+    let others = line_counts.split_off(top_authors.min(line_counts.len()));
+    let author_colors: HashMap<_, _> = line_counts
+        .iter()
+        .enumerate()
+        .map(|(index, (author, _))| (*author, color::palette(index)))
+        .collect();
+
+    let mut overlay = LineOverlay::default();
+    for (path, authors) in &blames {
+        for (index, author) in authors.iter().enumerate() {
+            let color = author_colors.get(author.as_str()).copied().unwrap_or(OTHER);
+            overlay.set(path, index as u32 + 1, color);
+        }
+    }
+    overlay.legend = line_counts
+        .iter()
+        .map(|(author, lines)| (author_colors[author], format!("{author} ({lines} lines)")))
+        .collect();
+    if !others.is_empty() {
+        let lines: usize = others.iter().map(|(_, lines)| lines).sum();
+        overlay.legend.push((
+            OTHER,
+            format!("{} other authors ({lines} lines)", others.len()),
+        ));
+    }
+    Ok(overlay)
+}
+
+/// Return the author of each line of the file at `relative_path` in `dir`,
+/// or `None` if it isn't tracked by git, like files in archives.
+fn blame(dir: &Path, relative_path: &Path) -> anyhow::Result<Option<Authors>> {
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    let output = Command::new("git")
+        .arg("blame")
+        .arg("--line-porcelain")
+        .arg("--")
+        .arg(relative_path)
+        .current_dir(dir)
+        .output()
+        .context("Failed to run git, is it installed?")?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(Some(parse_line_porcelain(&String::from_utf8_lossy(
+        &output.stdout,
+    ))))
+}
+
+/// Parse the output of `git blame --line-porcelain`, where each line of the file is preceded by a full header.
+fn parse_line_porcelain(output: &str) -> Authors {
+    let mut authors = Authors::new();
+    let mut author = None;
+    for line in output.lines() {
+        if let Some(name) = line.strip_prefix("author ") {
+            author = Some(name);
+        } else if line.starts_with('\t') {
+            authors.push(author.take().unwrap_or_default().to_owned());
+        }
+    }
+    authors
+}
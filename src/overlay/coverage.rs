@@ -0,0 +1,242 @@
+//! Read line coverage from lcov, Cobertura XML or `llvm-cov export` JSON reports.
+use crate::overlay::LineOverlay;
+use anyhow::{bail, Context};
+use image::Rgb;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// The color of lines that were executed, with all of their branches taken.
+pub const COVERED: Rgb<u8> = Rgb([60, 180, 75]);
+/// The color of lines that were executed, but with some of their branches or regions never taken.
+pub const PARTIAL: Rgb<u8> = Rgb([255, 225, 25]);
+/// The color of lines that were never executed.
+pub const UNCOVERED: Rgb<u8> = Rgb([230, 25, 75]);
+
+/// The coverage of a single line, accumulated across all records mentioning it.
+#[derive(Debug, Default, Clone, Copy)]
+struct Line {
+    hits: u64,
+    branches: u32,
+    branches_taken: u32,
+}
+
+/// A segment of the output of `llvm-cov export`, marking the start of a region of code.
+struct Segment {
+    line: u32,
+    column: u32,
+    count: u64,
+    has_count: bool,
+    is_region_entry: bool,
+    is_gap: bool,
+}
+
+type Report = HashMap<PathBuf, BTreeMap<u32, Line>>;
+
+/// Load the coverage report at `path`, detecting its format from its contents.
+pub fn load(path: &Path) -> anyhow::Result<LineOverlay> {
+    let input = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read coverage report at {path:?}"))?;
+    let report = match input.trim_start().chars().next() {
+        Some('<') => parse_cobertura(&input),
+        Some('{') => parse_llvm_cov_json(&input)?,
+        Some(_) if input.lines().any(|line| line.starts_with("SF:")) => parse_lcov(&input),
+        _ => bail!(
+            "Could not detect the format of the coverage report at {path:?}, \
+             expected lcov, Cobertura XML or llvm-cov JSON"
+        ),
+    };
+    Ok(to_overlay(report))
+}
+
+fn to_overlay(report: Report) -> LineOverlay {
+    let mut overlay = LineOverlay {
+        legend: vec![
+            (COVERED, "covered".into()),
+            (PARTIAL, "partially covered".into()),
+            (UNCOVERED, "not covered".into()),
+        ],
+        ..Default::default()
+    };
+    for (path, lines) in report {
+        for (number, line) in lines {
+            let color = if line.hits == 0 {
+                UNCOVERED
+            } else if line.branches_taken < line.branches {
+                PARTIAL
+            } else {
+                COVERED
+            };
+            overlay.set(&path, number, color);
+        }
+    }
+    overlay
+}
+
+/// Parse the `SF`, `DA` and `BRDA` records of an lcov tracefile.
+fn parse_lcov(input: &str) -> Report {
+    let mut report = Report::new();
+    let mut file = None;
+    for record in input.lines().map(str::trim) {
+        let (kind, value) = record.split_once(':').unwrap_or((record, ""));
+        match kind {
+            "SF" => file = Some(report.entry(PathBuf::from(value)).or_default()),
+            "end_of_record" => file = None,
+            "DA" => {
+                let mut fields = value.split(',');
+                if let (Some(file), Some(Ok(number)), Some(Ok(hits))) = (
+                    file.as_mut(),
+                    fields.next().map(str::parse::<u32>),
+                    fields.next().map(str::parse::<u64>),
+                ) {
+                    file.entry(number).or_default().hits += hits;
+                }
+            }
+            "BRDA" => {
+                let mut fields = value.split(',');
+                if let (Some(file), Some(Ok(number)), Some(taken)) = (
+                    file.as_mut(),
+                    fields.next().map(str::parse::<u32>),
+                    fields.nth(2),
+                ) {
+                    let line = file.entry(number).or_default();
+                    line.branches += 1;
+                    if !matches!(taken, "-" | "0") {
+                        line.branches_taken += 1;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    report
+}
+
+/// Parse the `class` and `line` elements of a Cobertura XML report.
+fn parse_cobertura(input: &str) -> Report {
+    let tag = regex::Regex::new(r"<(class|line)\s([^>]*)>").expect("valid regex");
+    let attribute = regex::Regex::new(r#"([\w:-]+)\s*=\s*"([^"]*)""#).expect("valid regex");
+    let mut report = Report::new();
+    let mut file = None;
+    for captures in tag.captures_iter(input) {
+        let attributes = xml_attributes(&attribute, &captures[2]);
+        if &captures[1] == "class" {
+            file = attributes
+                .get("filename")
+                .map(|filename| report.entry(PathBuf::from(filename)).or_default());
+            continue;
+        }
+        let (Some(file), Some(Ok(number))) = (
+            file.as_mut(),
+            attributes.get("number").map(|n| n.parse::<u32>()),
+        ) else {
+            continue;
+        };
+        let line = file.entry(number).or_default();
+        line.hits += attributes
+            .get("hits")
+            .and_then(|hits| hits.parse::<u64>().ok())
+            .unwrap_or(0);
+        // condition-coverage looks like `50% (1/2)`
+        if let Some((taken, total)) = attributes
+            .get("condition-coverage")
+            .and_then(|coverage| coverage.split_once('('))
+            .and_then(|(_, fraction)| fraction.trim_end_matches(')').split_once('/'))
+        {
+            line.branches += total.trim().parse::<u32>().unwrap_or(0);
+            line.branches_taken += taken.trim().parse::<u32>().unwrap_or(0);
+        }
+    }
+    report
+}
+
+/// Return the `name="value"` pairs in `attributes`, as matched by the `attribute` regex, with entities unescaped.
+fn xml_attributes(attribute: &regex::Regex, attributes: &str) -> HashMap<String, String> {
+    attribute
+        .captures_iter(attributes)
+        .map(|captures| {
+            let value = captures[2]
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&");
+            (captures[1].to_owned(), value)
+        })
+        .collect()
+}
+
+/// Parse the segments of all files in the output of `llvm-cov export -format=text`.
+///
+/// A line counts as executed if any region on it was, and is partially covered if any of its regions was not.
+fn parse_llvm_cov_json(input: &str) -> anyhow::Result<Report> {
+    let export: serde_json::Value =
+        serde_json::from_str(input).context("Failed to parse llvm-cov JSON")?;
+    let mut report = Report::new();
+    let files = export["data"]
+        .as_array()
+        .context("Expected a 'data' array in llvm-cov JSON")?
+        .iter()
+        .filter_map(|data| data["files"].as_array())
+        .flatten();
+    for file in files {
+        let Some(filename) = file["filename"].as_str() else {
+            continue;
+        };
+        let lines = report.entry(PathBuf::from(filename)).or_default();
+
+        // segments are `[line, column, count, has_count, is_region_entry, is_gap_region]`
+        let mut wrapped_count = None;
+        let mut segments = file["segments"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|segment| {
+                Some(Segment {
+                    line: segment[0].as_u64()? as u32,
+                    column: segment[1].as_u64()? as u32,
+                    count: segment[2].as_u64()?,
+                    has_count: segment[3].as_bool()?,
+                    is_region_entry: segment[4].as_bool()?,
+                    is_gap: segment
+                        .get(5)
+                        .and_then(|gap| gap.as_bool())
+                        .unwrap_or(false),
+                })
+            })
+            .peekable();
+        while let Some(&Segment {
+            line: number,
+            column,
+            ..
+        }) = segments.peek()
+        {
+            // a region starting at the very beginning of the line replaces the one wrapping into it.
+            let mut counts: Vec<u64> = wrapped_count.filter(|_| column > 1).into_iter().collect();
+            while let Some(segment) = segments.next_if(|segment| segment.line == number) {
+                if segment.has_count && segment.is_region_entry && !segment.is_gap {
+                    counts.push(segment.count);
+                }
+                wrapped_count = segment.has_count.then_some(segment.count);
+            }
+            if !counts.is_empty() {
+                let line = lines.entry(number).or_default();
+                line.hits += counts.iter().max().copied().unwrap_or(0);
+                line.branches += counts.len() as u32;
+                line.branches_taken += counts.iter().filter(|count| **count > 0).count() as u32;
+            }
+
+            // lines between segments are covered by the region that was active last.
+            let next_number = segments.peek().map_or(number + 1, |segment| segment.line);
+            if let Some(count) = wrapped_count {
+                for number in number + 1..next_number {
+                    let line = lines.entry(number).or_default();
+                    line.hits += count;
+                    line.branches += 1;
+                    line.branches_taken += u32::from(count > 0);
+                }
+            }
+        }
+    }
+    Ok(report)
+}
@@ -0,0 +1,215 @@
+//! Read compiler and linter diagnostics from SARIF files or the output of `cargo check --message-format=json`.
+use crate::overlay::LineOverlay;
+use anyhow::Context;
+use image::Rgb;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// How bad a diagnostic is, from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Notes, help and other informational messages.
+    Note,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// The color of lines with diagnostics of this severity.
+    pub fn color(&self) -> Rgb<u8> {
+        match self {
+            Severity::Note => Rgb([67, 99, 216]),
+            Severity::Warning => Rgb([245, 130, 49]),
+            Severity::Error => Rgb([230, 25, 75]),
+        }
+    }
+
+    fn from_level(level: &str) -> Option<Self> {
+        Some(match level {
+            "error" | "error: internal compiler error" => Severity::Error,
+            "warning" => Severity::Warning,
+            "note" | "help" | "failure-note" => Severity::Note,
+            _ => return None,
+        })
+    }
+}
+
+/// The first and last line of each diagnostic along with its severity, by path.
+type Report = HashMap<PathBuf, Vec<(u32, u32, Severity)>>;
+
+/// Load diagnostics from the file at `path`, or from stdin if it is `-`, detecting the format from its contents.
+///
+/// Lines are colored by the most severe diagnostic touching them.
+pub fn load(path: &Path) -> anyhow::Result<LineOverlay> {
+    let mut input = String::new();
+    if path == Path::new("-") {
+        std::io::stdin()
+            .read_to_string(&mut input)
+            .context("Failed to read diagnostics from stdin")?;
+    } else {
+        input = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read diagnostics at {path:?}"))?;
+    }
+
+    let mut report = Report::new();
+    let mut counts = HashMap::<Severity, usize>::new();
+    match serde_json::from_str::<serde_json::Value>(&input) {
+        Ok(sarif) if sarif.get("runs").is_some() => parse_sarif(&sarif, &mut report, &mut counts),
+        _ => {
+            for line in input
+                .lines()
+                .filter(|line| line.trim_start().starts_with('{'))
+            {
+                let message: serde_json::Value = serde_json::from_str(line)
+                    .context("Failed to parse line of cargo JSON output")?;
+                parse_cargo_message(&message, &mut report, &mut counts);
+            }
+        }
+    }
+
+    let mut overlay = LineOverlay::default();
+    for severity in [Severity::Error, Severity::Warning, Severity::Note] {
+        let label = match severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        };
+        let count = counts.get(&severity).copied().unwrap_or(0);
+        let plural = if count == 1 { "" } else { "s" };
+        overlay
+            .legend
+            .push((severity.color(), format!("{count} {label}{plural}")));
+    }
+    for (path, mut ranges) in report {
+        // later ranges replace the colors of earlier ones, so lines end up with the most severe diagnostic.
+        ranges.sort_by_key(|(_, _, severity)| *severity);
+        for (first, last, severity) in ranges {
+            overlay.set_range(&path, first, last, severity.color());
+        }
+    }
+    Ok(overlay)
+}
+
+fn record(report: &mut Report, path: PathBuf, first: u32, last: u32, severity: Severity) {
+    report
+        .entry(path)
+        .or_default()
+        .push((first, last.max(first), severity));
+}
+
+/// Record the primary spans of a `compiler-message`, ignoring all other messages like build artifacts.
+fn parse_cargo_message(
+    message: &serde_json::Value,
+    report: &mut Report,
+    counts: &mut HashMap<Severity, usize>,
+) {
+    if message["reason"] != "compiler-message" {
+        return;
+    }
+    let message = &message["message"];
+    let Some(severity) = message["level"].as_str().and_then(Severity::from_level) else {
+        return;
+    };
+    let spans = message["spans"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter(|span| span["is_primary"].as_bool().unwrap_or(false));
+    let mut has_location = false;
+    for span in spans {
+        let (Some(file_name), Some(first)) =
+            (span["file_name"].as_str(), span["line_start"].as_u64())
+        else {
+            continue;
+        };
+        let last = span["line_end"].as_u64().unwrap_or(first);
+        record(
+            report,
+            file_name.into(),
+            first as u32,
+            last as u32,
+            severity,
+        );
+        has_location = true;
+    }
+    if has_location {
+        *counts.entry(severity).or_default() += 1;
+    }
+}
+
+/// Record the physical locations of the results of all runs in a SARIF log.
+fn parse_sarif(
+    sarif: &serde_json::Value,
+    report: &mut Report,
+    counts: &mut HashMap<Severity, usize>,
+) {
+    let results = sarif["runs"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|run| run["results"].as_array())
+        .flatten();
+    for result in results {
+        // SARIF defaults to warnings if no level is given, and 'none' is informational.
+        let severity = match result["level"].as_str() {
+            None => Severity::Warning,
+            Some("none") => Severity::Note,
+            Some(level) => Severity::from_level(level).unwrap_or(Severity::Warning),
+        };
+        let locations = result["locations"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .map(|location| &location["physicalLocation"]);
+        let mut has_location = false;
+        for location in locations {
+            let (Some(uri), Some(first)) = (
+                location["artifactLocation"]["uri"].as_str(),
+                location["region"]["startLine"].as_u64(),
+            ) else {
+                continue;
+            };
+            let last = location["region"]["endLine"].as_u64().unwrap_or(first);
+            record(
+                report,
+                uri_to_path(uri),
+                first as u32,
+                last as u32,
+                severity,
+            );
+            has_location = true;
+        }
+        if has_location {
+            *counts.entry(severity).or_default() += 1;
+        }
+    }
+}
+
+/// Turn a SARIF artifact URI like `file:///src/a%20b.rs` or `src/main.rs` into a path.
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut bytes = path.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = [bytes.next(), bytes.next()];
+            if let [Some(high), Some(low)] = hex {
+                if let Ok(value) =
+                    u8::from_str_radix(&format!("{}{}", high as char, low as char), 16)
+                {
+                    decoded.push(value);
+                    continue;
+                }
+            }
+            decoded.push(byte);
+            decoded.extend(hex.into_iter().flatten());
+        } else {
+            decoded.push(byte);
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&decoded).into_owned())
+}
@@ -0,0 +1,217 @@
+//! Colors supplied from outside of the source code, like coverage reports, to mix into individual lines.
+use crate::render::caption::LegendEntry;
+use crate::render::color;
+use image::Rgb;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+
+pub mod annotations;
+pub mod blame;
+pub mod coverage;
+pub mod diagnostics;
+pub mod profile;
+
+/// Numbers attached to the 1-based lines of files, by path.
+pub type Weights = HashMap<PathBuf, BTreeMap<u32, f64>>;
+
+/// How weights are mapped onto the position in a gradient.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Scale {
+    /// Positions are proportional to the weight.
+    Linear,
+    /// Positions are proportional to the logarithm of the weight, to make small weights visible next to large ones.
+    Log,
+}
+
+/// Runs of lines with the same color, keyed by their 1-based first line and holding their last line and color.
+///
+/// Runs never overlap, so large ranges of lines take no more space than a single line.
+type Runs = BTreeMap<u32, (u32, Rgb<u8>)>;
+
+/// Colors to mix into individual lines of files.
+#[derive(Debug, Default, Clone)]
+pub struct LineOverlay {
+    /// The colored lines of each file by path.
+    files: HashMap<PathBuf, Runs>,
+    /// What the colors of this overlay stand for.
+    pub legend: Vec<LegendEntry>,
+}
+
+impl LineOverlay {
+    /// Color the 1-based `line` of the file at `path`, replacing any previous color.
+    pub fn set(&mut self, path: &Path, line: u32, color: Rgb<u8>) {
+        self.set_range(path, line, line, color)
+    }
+
+    /// Color all lines from the 1-based `first` to the `last` line, inclusive, of the file at `path`.
+    pub fn set_range(&mut self, path: &Path, first: u32, last: u32, color: Rgb<u8>) {
+        if first == 0 || last < first {
+            return;
+        }
+        let runs = self.files.entry(normalize(path)).or_default();
+        // as runs don't overlap, the ones overlapping the new run are the last ones starting before its end.
+        let overlapping: Vec<_> = runs
+            .range(..=last)
+            .rev()
+            .take_while(|(_, (run_last, _))| *run_last >= first)
+            .map(|(run_first, run)| (*run_first, *run))
+            .collect();
+        for (run_first, (run_last, run_color)) in overlapping {
+            runs.remove(&run_first);
+            if run_first < first {
+                runs.insert(run_first, (first - 1, run_color));
+            }
+            if run_last > last {
+                runs.insert(last + 1, (run_last, run_color));
+            }
+        }
+        runs.insert(first, (last, color));
+    }
+
+    /// Color each line with `weights` by mapping its weight from the range of all weights onto `gradient`.
+    /// The range starts at `min` if set, or at the smallest weight otherwise.
+    ///
+    /// The legend shows the weights at evenly spaced positions of the gradient, formatted with `label`.
+    pub fn from_weights(
+        weights: &Weights,
+        gradient: &[Rgb<u8>],
+        scale: Scale,
+        min: Option<f64>,
+        label: impl Fn(f64) -> String,
+    ) -> Self {
+        let ranges: Vec<_> = weights
+            .iter()
+            .flat_map(|(path, lines)| {
+                lines
+                    .iter()
+                    .map(move |(line, weight)| (path.as_path(), *line, *line, *weight))
+            })
+            .collect();
+        Self::from_weighted_ranges(&ranges, gradient, scale, min, label)
+    }
+
+    /// Like [`LineOverlay::from_weights()`], but with a weight for each range of lines from the 1-based first
+    /// to the last line, inclusive, of the file at a path.
+    pub(crate) fn from_weighted_ranges(
+        ranges: &[(&Path, u32, u32, f64)],
+        gradient: &[Rgb<u8>],
+        scale: Scale,
+        min: Option<f64>,
+        label: impl Fn(f64) -> String,
+    ) -> Self {
+        let all_weights = || ranges.iter().map(|(_, _, _, weight)| *weight);
+        let min = min.unwrap_or_else(|| all_weights().fold(f64::INFINITY, f64::min));
+        let max = all_weights().fold(f64::NEG_INFINITY, f64::max);
+        let (min, max) = if min <= max { (min, max) } else { (0.0, 0.0) };
+
+        let transform = |weight: f64| match scale {
+            Scale::Linear => weight - min,
+            Scale::Log => (weight - min).ln_1p(),
+        };
+        let range = transform(max).max(f64::EPSILON);
+        let position = |weight: f64| (transform(weight.max(min)) / range) as f32;
+
+        let mut overlay = LineOverlay::default();
+        for (path, first, last, weight) in ranges {
+            overlay.set_range(
+                path,
+                *first,
+                *last,
+                color::gradient(gradient, position(*weight)),
+            );
+        }
+
+        let steps = 4;
+        overlay.legend = (0..=steps)
+            .map(|step| {
+                let position = step as f64 / steps as f64;
+                let weight = match scale {
+                    Scale::Linear => min + (max - min) * position,
+                    Scale::Log => min + (range * position).exp_m1(),
+                };
+                (color::gradient(gradient, position as f32), label(weight))
+            })
+            .collect();
+        overlay
+    }
+
+    /// Return true if no line was colored.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Return the colors of the lines of the file at `relative_path`, which is relative to `root`.
+    pub fn lines(&self, root: &RootPaths, relative_path: &Path) -> Option<Lines<'_>> {
+        root.bases
+            .iter()
+            .find_map(|base| self.files.get(&normalize(&base.join(relative_path))))
+            .map(Lines)
+    }
+
+    /// Return the color of the 1-based `line` of the file at `relative_path`, which is relative to `root`.
+    pub fn color(&self, root: &RootPaths, relative_path: &Path, line: u32) -> Option<Rgb<u8>> {
+        self.lines(root, relative_path)?.get(line)
+    }
+}
+
+/// The colored lines of a single file in a [`LineOverlay`].
+#[derive(Debug, Clone, Copy)]
+pub struct Lines<'a>(&'a Runs);
+
+impl Lines<'_> {
+    /// Return the color of the 1-based `line`, if it has one.
+    pub fn get(&self, line: u32) -> Option<Rgb<u8>> {
+        self.0
+            .range(..=line)
+            .next_back()
+            .filter(|(_, (last, _))| *last >= line)
+            .map(|(_, (_, color))| *color)
+    }
+}
+
+/// The directories which paths in overlays may be relative to for the files of a rendered directory.
+#[derive(Debug, Clone)]
+pub struct RootPaths {
+    /// The rendered directory itself as empty path, followed by its absolute path and its path relative
+    /// to the root of the repository containing it, if known.
+    bases: Vec<PathBuf>,
+}
+
+impl RootPaths {
+    /// Resolve the directories that paths in overlays may be relative to for the files of `dir`.
+    pub fn new(dir: &Path) -> Self {
+        let mut bases = vec![PathBuf::new()];
+        let absolute = dir.canonicalize().unwrap_or_else(|_| dir.to_owned());
+        if absolute.is_absolute() {
+            let in_repo = absolute
+                .ancestors()
+                .find(|ancestor| ancestor.join(".git").exists())
+                .and_then(|repo| absolute.strip_prefix(repo).ok())
+                .filter(|in_repo| !in_repo.as_os_str().is_empty())
+                .map(ToOwned::to_owned);
+            bases.push(absolute);
+            bases.extend(in_repo);
+        }
+        RootPaths { bases }
+    }
+}
+
+/// Remove `.` components and resolve `..` components lexically, so paths written differently compare equal.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
@@ -0,0 +1,58 @@
+//! Read sample counts of source lines from collapsed stacks, as produced by the `stackcollapse` scripts of flamegraph.
+use crate::overlay::{LineOverlay, Scale, Weights};
+use crate::render::color;
+use anyhow::{bail, Context};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Load the collapsed stacks at `path` and color each line by the amount of samples it was on the stack for.
+///
+/// Each line of the input is a `;`-separated stack of frames followed by a sample count, like
+/// `main (src/main.rs:10);render (src/render/function.rs:42) 17`. Frames without a `file:line`
+/// location are skipped, and lines appearing multiple times in one stack count only once.
+/// Data recorded with `perf` can be turned into this format with `perf script -F +srcline | stackcollapse-perf.pl`.
+pub fn load(path: &Path, scale: Scale) -> anyhow::Result<LineOverlay> {
+    let input = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read collapsed stacks at {path:?}"))?;
+    let weights = parse(&input)?;
+    if weights.is_empty() {
+        bail!("Did not find a single frame with a 'file:line' location in {path:?}")
+    }
+    Ok(LineOverlay::from_weights(
+        &weights,
+        &color::FLAME_GRADIENT,
+        scale,
+        Some(0.0),
+        |samples| format!("{samples:.0} samples"),
+    ))
+}
+
+fn parse(input: &str) -> anyhow::Result<Weights> {
+    let location =
+        regex::Regex::new(r"([^\s;:()\[\]]+\.[\w+]+):(\d+)(?::\d+)?").expect("valid regex");
+    let mut weights = Weights::new();
+    for (line_number, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (stack, samples) = line
+            .rsplit_once(' ')
+            .with_context(|| format!("Expected 'stack count' in line {}", line_number + 1))?;
+        let samples: f64 = samples
+            .parse()
+            .with_context(|| format!("Invalid sample count in line {}", line_number + 1))?;
+
+        let locations: BTreeSet<(PathBuf, u32)> = stack
+            .split(';')
+            .filter_map(|frame| {
+                let captures = location.captures(frame)?;
+                Some((PathBuf::from(&captures[1]), captures[2].parse().ok()?))
+            })
+            .collect();
+        for (path, line) in locations {
+            *weights.entry(path).or_default().entry(line).or_default() += samples;
+        }
+    }
+    Ok(weights)
+}
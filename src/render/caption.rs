@@ -0,0 +1,303 @@
+use crate::render::chunk::put_scaled_char_in_image;
+use crate::render::{color, BgColor, FgColor};
+use clap::ValueEnum;
+use image::{ImageBuffer, Rgb};
+use memmap2::MmapMut;
+use std::str::FromStr;
+use syntect::highlighting::{Color, Highlighter, Style, Theme};
+use syntect::parsing::ScopeStack;
+use unifont_bitmap::Unifont;
+
+/// The height of a unifont glyph in pixels, before scaling.
+const GLYPH_HEIGHT: u32 = 16;
+/// The width of a narrow unifont glyph in pixels, before scaling.
+const GLYPH_WIDTH: u32 = 8;
+
+/// Where to place the caption band relative to the rendered code.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Position {
+    /// Above the code, like a header.
+    Top,
+    /// Below the code, like a footer.
+    Bottom,
+}
+
+/// Configure the caption band that is added to the rendered image.
+#[derive(Debug, Copy, Clone)]
+pub struct Caption<'a> {
+    /// Whether the band is a header or a footer.
+    pub position: Position,
+    /// Written in the first line of the band. The repository name is used if unset.
+    pub title: Option<&'a str>,
+    /// The name of the rendered repository.
+    pub repo_name: &'a str,
+    /// The date of the render, written as is.
+    pub date: &'a str,
+    /// The factor by which glyphs are scaled up.
+    ///
+    /// '0' derives the scale from the width of the image.
+    pub scale: u32,
+}
+
+/// Numbers about the finished render that are written into the caption.
+pub(crate) struct Stats<'a> {
+    pub theme: &'a str,
+    pub fg_color: FgColor,
+    pub bg_color: BgColor,
+    pub total_lines: u32,
+    pub total_files: usize,
+}
+
+/// A color along with a description of what it stands for.
+pub type LegendEntry = (Rgb<u8>, String);
+
+/// Produce the legend entries explaining the colors of the given foreground and background modes.
+pub(crate) fn legend(
+    fg_color: FgColor,
+    bg_color: BgColor,
+    theme: &Theme,
+    color_modulation: f32,
+    depth_gradient: &[Rgb<u8>],
+    gradient_max_depth: u32,
+) -> Vec<LegendEntry> {
+    let highlighter = Highlighter::new(theme);
+    let mut entries = Vec::new();
+
+    let default_style = style_for_scope(&highlighter, "");
+    match bg_color {
+        BgColor::Style | BgColor::HelixEditor => entries.push((
+            bg_color.to_rgb(default_style, 0, color_modulation, None),
+            "background".into(),
+        )),
+        // the colors depend on the files, and are added by the caller.
+        BgColor::Complexity | BgColor::Owner => {}
+        BgColor::StyleCheckerboardDarken | BgColor::StyleCheckerboardBrighten => {
+            entries.push((
+                bg_color.to_rgb(default_style, 0, color_modulation, None),
+                "even files".into(),
+            ));
+            entries.push((
+                bg_color.to_rgb(default_style, 1, color_modulation, None),
+                "odd files".into(),
+            ));
+        }
+    }
+
+    match fg_color {
+        FgColor::Style | FgColor::StyleAsciiBrightness => {
+            for (scope, label) in [
+                ("", "text"),
+                ("comment", "comment"),
+                ("string", "string"),
+                ("keyword", "keyword"),
+                ("constant.numeric", "number"),
+                ("entity.name.function", "function"),
+            ] {
+                let fg = style_for_scope(&highlighter, scope).foreground;
+                let color = Rgb([fg.r, fg.g, fg.b]);
+                if !entries.iter().any(|(c, _)| *c == color) {
+                    entries.push((color, label.into()));
+                }
+            }
+        }
+        FgColor::IndentationDepth | FgColor::BracketDepth => {
+            let max_depth = gradient_max_depth.max(1);
+            let steps = max_depth.min(4);
+            for step in 0..=steps {
+                let depth = max_depth * step / steps;
+                let label = if depth == max_depth {
+                    format!("depth {depth}+")
+                } else {
+                    format!("depth {depth}")
+                };
+                entries.push((
+                    color::gradient(depth_gradient, depth as f32 / max_depth as f32),
+                    label,
+                ));
+            }
+        }
+    }
+    entries
+}
+
+/// Return the style of text without any scope in the given theme.
+pub(crate) fn default_style(theme: &Theme) -> Style {
+    style_for_scope(&Highlighter::new(theme), "")
+}
+
+fn style_for_scope(highlighter: &Highlighter<'_>, scope: &str) -> Style {
+    let stack = ScopeStack::from_str(scope).unwrap_or_default();
+    highlighter.style_for_stack(stack.as_slice())
+}
+
+/// Return a new image which is `img` with a caption band added above or below it.
+pub(crate) fn append(
+    img: ImageBuffer<Rgb<u8>, MmapMut>,
+    caption: Caption<'_>,
+    stats: Stats<'_>,
+    legend: &[LegendEntry],
+    theme: &Theme,
+) -> anyhow::Result<ImageBuffer<Rgb<u8>, MmapMut>> {
+    let background = theme_color(theme.settings.background, Rgb([0, 0, 0]));
+    let text_color = theme_color(theme.settings.foreground, Rgb([255, 255, 255]));
+
+    let width = img.width();
+    let scale = if caption.scale == 0 {
+        (width / 1600).max(1)
+    } else {
+        caption.scale
+    };
+    let padding = GLYPH_WIDTH * scale;
+    let line_advance = (GLYPH_HEIGHT + GLYPH_HEIGHT / 4) * scale;
+
+    let mut lines = vec![vec![Item::Text(
+        caption.title.unwrap_or(caption.repo_name).to_owned(),
+    )]];
+
+    // lay out statistics and legend, wrapping entries that don't fit into the image width.
+    let stats = [
+        caption.repo_name.to_owned(),
+        caption.date.to_owned(),
+        stats.theme.to_owned(),
+        format!("{} lines", stats.total_lines),
+        format!("{} files", stats.total_files),
+        format!("fg: {}", value_name(stats.fg_color)),
+        format!("bg: {}", value_name(stats.bg_color)),
+    ];
+    let stats_len = stats.len();
+    let stats = stats.into_iter().enumerate().map(|(index, text)| {
+        let separator = if index + 1 == stats_len { "" } else { " | " };
+        vec![Item::Text(format!("{text}{separator}"))]
+    });
+    let legend = legend
+        .iter()
+        .map(|(color, label)| vec![Item::Swatch(*color), Item::Text(format!(" {label}   "))]);
+    for entries in [stats.collect::<Vec<_>>(), legend.collect()] {
+        let mut line = Vec::new();
+        let mut line_width = 0;
+        for entry in entries {
+            let entry_width = entry.iter().map(|item| item.width(scale)).sum::<u32>();
+            if !line.is_empty() && padding + line_width + entry_width > width {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+            }
+            line.extend(entry);
+            line_width += entry_width;
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+    }
+
+    let band_height = padding * 2 + lines.len() as u32 * line_advance;
+    let height = img.height() + band_height;
+    let row_bytes = width as usize * 3;
+    let mut raw = MmapMut::map_anon(row_bytes * height as usize)?;
+    let (img_start, band_y) = match caption.position {
+        Position::Top => (band_height as usize * row_bytes, 0),
+        Position::Bottom => (0, img.height()),
+    };
+    raw[img_start..][..img.as_raw().len()].copy_from_slice(img.as_raw());
+    drop(img);
+
+    let mut out = ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, raw)
+        .expect("correct size computation above");
+    for y in band_y..band_y + band_height {
+        for x in 0..width {
+            out.put_pixel(x, y, background);
+        }
+    }
+
+    let mut unifont = Unifont::open();
+    for (line_index, items) in lines.iter().enumerate() {
+        let y = band_y + padding + line_index as u32 * line_advance;
+        let mut x = padding;
+        for item in items {
+            match item {
+                Item::Text(text) => {
+                    for chr in text.chars() {
+                        if x >= width {
+                            break;
+                        }
+                        x += put_scaled_char_in_image(
+                            chr,
+                            &mut unifont,
+                            x,
+                            y,
+                            scale,
+                            &mut out,
+                            &background,
+                            &text_color,
+                        );
+                    }
+                }
+                Item::Swatch(color) => {
+                    // outline the swatch so colors matching the band remain visible.
+                    let size = GLYPH_HEIGHT * scale;
+                    for swatch_y in y..(y + size).min(height) {
+                        for swatch_x in x..(x + size).min(width) {
+                            let is_border = swatch_y < y + scale
+                                || swatch_y >= y + size - scale
+                                || swatch_x < x + scale
+                                || swatch_x >= x + size - scale;
+                            let color = if is_border { text_color } else { *color };
+                            out.put_pixel(swatch_x, swatch_y, color);
+                        }
+                    }
+                    x += size;
+                }
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Something drawn into a line of the caption.
+enum Item {
+    Text(String),
+    /// A square filled with the given color.
+    Swatch(Rgb<u8>),
+}
+
+impl Item {
+    /// The width of the item in pixels, assuming narrow glyphs.
+    fn width(&self, scale: u32) -> u32 {
+        match self {
+            Item::Text(text) => text.chars().count() as u32 * GLYPH_WIDTH * scale,
+            Item::Swatch(_) => GLYPH_HEIGHT * scale,
+        }
+    }
+}
+
+fn theme_color(color: Option<Color>, default: Rgb<u8>) -> Rgb<u8> {
+    color.map_or(default, |c| Rgb([c.r, c.g, c.b]))
+}
+
+fn value_name(value: impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map(|v| v.get_name().to_owned())
+        .unwrap_or_default()
+}
+
+/// Return today's date in UTC, formatted like `2022-10-31`.
+pub fn today() -> String {
+    let days = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() / (60 * 60 * 24)) as i64;
+
+    // convert days since the unix epoch into a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
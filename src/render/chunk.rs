@@ -1,9 +1,14 @@
-use crate::render::{BgColor, FgColor};
+use crate::overlay::{LineOverlay, RootPaths};
+use crate::render::complexity::Complexity;
+use crate::render::highlight::Highlighter;
+use crate::render::search::{LineTints, Pattern};
+use crate::render::{color, BgColor, FgColor};
 use bstr::ByteSlice;
 use image::{ImageBuffer, Rgb};
 use std::ops::{Deref, DerefMut};
 use std::path::Path;
 use syntect::highlighting::{Color, Style};
+use syntect::parsing::SyntaxSet;
 use unifont_bitmap::Unifont;
 
 /// The result of processing a chunk.
@@ -14,7 +19,7 @@ pub struct Outcome {
     pub background: Option<Rgb<u8>>,
 }
 
-pub struct Context {
+pub struct Context<'a> {
     pub column_width: u32,
     pub line_height: u32,
     pub char_width: u32,
@@ -27,11 +32,30 @@ pub struct Context {
     pub highlight_truncated_lines: bool,
 
     pub file_index: usize,
+    /// The color assigned to the file by background modes which color files individually.
+    pub file_color: Option<Rgb<u8>>,
     pub color_modulation: f32,
     pub tab_spaces: u32,
     pub readable: bool,
     pub show_filenames: bool,
     pub line_nums: bool,
+    pub depth_gradient: &'a [Rgb<u8>],
+    pub gradient_max_depth: u32,
+    pub highlight_patterns: &'a [Pattern],
+    pub highlight_whole_lines: bool,
+    pub line_overlays: &'a [LineOverlay],
+    /// The directories which paths in `line_overlays` may be relative to for the root of the file.
+    pub overlay_root: &'a RootPaths,
+    /// The path of the file relative to its root, to find its lines in `line_overlays`.
+    pub relative_path: &'a Path,
+    /// The color to paint replacement characters with, if they should stand out.
+    pub replacement_color: Option<Rgb<u8>>,
+    /// If true, the last line of the file is the marker of a truncated file.
+    pub truncated: bool,
+    /// If true, the file is a hex dump and each hex digit is drawn with a brightness proportional to its value.
+    pub hex_density: bool,
+    /// How much to dim all pixels of the file, if at all.
+    pub dim: Option<f32>,
 }
 
 /// Return the `(x, y)` offsets to apply to the given line, to wrap columns of lines into the
@@ -60,6 +84,81 @@ fn ensure_width(str: String, width: u32) -> String {
     spaces + &str
 }
 
+/// Return the number of columns the leading whitespace of `line` spans.
+pub(crate) fn indentation_columns(line: &str, tab_spaces: u32) -> u32 {
+    let tab_spaces = tab_spaces.max(1);
+    let mut columns = 0;
+    for chr in line.chars() {
+        match chr {
+            ' ' => columns += 1,
+            '\t' => columns += tab_spaces - (columns % tab_spaces),
+            _ => break,
+        }
+    }
+    columns
+}
+
+/// Return the bracket depth after `chr` if the depth before it was `depth`.
+fn bracket_depth_after_char(chr: char, depth: u32) -> u32 {
+    match chr {
+        '{' | '(' | '[' => depth + 1,
+        '}' | ')' | ']' => depth.saturating_sub(1),
+        _ => depth,
+    }
+}
+
+/// Return the bracket depth at the end of `line` if the depth at its start was `depth`.
+fn bracket_depth_after(line: &str, depth: u32) -> u32 {
+    line.chars()
+        .fold(depth, |depth, chr| bracket_depth_after_char(chr, depth))
+}
+
+/// Split `line` into the part to highlight and the part which fits into a column of `column_width` characters,
+/// along with the amount of characters in the whole line.
+fn split_line(
+    line: &[u8],
+    column_width: u32,
+    highlight_truncated_lines: bool,
+) -> (&str, &str, usize) {
+    let line = line.to_str().expect("UTF-8 was source");
+    let mut num_chars = 0;
+    let mut chars = line.chars();
+    let bytes_till_char_limit: usize = chars
+        .by_ref()
+        .take(column_width as usize)
+        .map(|c| {
+            num_chars += 1;
+            c.len_utf8()
+        })
+        .sum();
+    num_chars += chars.count();
+    let possibly_truncated_line = if num_chars >= column_width as usize {
+        &line[..bytes_till_char_limit]
+    } else {
+        line
+    };
+    (
+        if highlight_truncated_lines {
+            possibly_truncated_line
+        } else {
+            line
+        },
+        possibly_truncated_line,
+        num_chars,
+    )
+}
+
+/// Highlight `line`, unless it is so long that it could lock up the parser.
+fn highlight<'line>(
+    highlighter: &mut Highlighter<'_>,
+    line: &'line str,
+    ss: &SyntaxSet,
+) -> Result<Option<Vec<(Style, &'line str)>>, syntect::Error> {
+    (line.len() <= 1024 * 16)
+        .then(|| highlighter.highlight_line(line, ss))
+        .transpose()
+}
+
 /// Renders text onto an existing image.
 ///
 /// Images of proper dimensions must be pre-allocated before calling this function.
@@ -70,7 +169,8 @@ pub fn process<C>(
     filepath: &Path,
     content: &str,
     img: &mut ImageBuffer<Rgb<u8>, C>,
-    mut highlight: impl FnMut(&str) -> Result<Vec<(Style, &str)>, syntect::Error>,
+    highlighter: &mut Highlighter<'_>,
+    ss: &SyntaxSet,
     Context {
         column_width,
         line_height,
@@ -82,12 +182,24 @@ pub fn process<C>(
         fg_color,
         bg_color,
         file_index,
+        file_color,
         color_modulation,
         tab_spaces,
         readable,
         show_filenames,
         line_nums,
-    }: Context,
+        depth_gradient,
+        gradient_max_depth,
+        highlight_patterns,
+        highlight_whole_lines,
+        line_overlays,
+        overlay_root,
+        relative_path,
+        replacement_color,
+        truncated,
+        hex_density,
+        dim,
+    }: Context<'_>,
 ) -> anyhow::Result<Outcome>
 where
     C: Deref<Target = [u8]>,
@@ -103,123 +215,75 @@ where
         0
     };
 
-    let style = highlight(" ")?[0].0;
+    let first_line_num = line_num;
+    let style = highlighter.highlight_line(" ", ss)?[0].0;
     let initial_forground_color = Rgb([style.foreground.r, style.foreground.g, style.foreground.b]);
 
-    // write the filename
-    if show_filenames {
-        // get background color
-        // let style = highlight(" ")?[0].0;
-        // println!("style: {:#?}", style);
-        let mut background = None::<Rgb<u8>>;
-        let background =
-            background.get_or_insert_with(|| bg_color.to_rgb(style, file_index, color_modulation));
-
-        // figure out where in the image to write
-        let actual_line = line_num % total_line_count;
-        let (cur_column_x_offset, cur_y) = calc_offsets(
-            actual_line,
-            lines_per_column,
-            column_width * char_width,
-            line_height,
-        );
-
-        // write filename on image
-        // let char_color = Rgb([255, 255, 255]);
-        let mut cur_line_x = 0;
-        for chr in filepath.to_str().unwrap().chars() {
-            if readable {
-                put_readable_char_in_image(
-                    chr,
-                    &mut unifont,
-                    cur_column_x_offset + cur_line_x * char_width,
-                    cur_y,
-                    img,
-                    &background,
-                    &initial_forground_color,
-                    &mut cur_line_x,
-                );
-            } else {
-                // Fill the char space with a solid color.
-                let img_x = cur_column_x_offset + cur_line_x;
-                put_solid_char_in_image(
-                    img_x,
-                    cur_y,
-                    img,
-                    initial_forground_color,
-                    line_height,
-                    char_width,
-                    &mut cur_line_x,
-                );
-            }
-        }
-
-        // Fill the rest of the line with the background color.
-        if readable {
-            while cur_line_x < column_width {
-                put_readable_char_in_image(
-                    ' ',
-                    &mut unifont,
-                    cur_column_x_offset + cur_line_x * char_width,
-                    cur_y,
-                    img,
-                    background,
-                    background,
-                    &mut cur_line_x,
-                );
-            }
-        } else {
-            while cur_line_x < column_width * char_width {
-                // Fill the char space with a solid color.
-                let img_x = cur_column_x_offset + cur_line_x;
-                put_solid_char_in_image(
-                    img_x,
-                    cur_y,
-                    img,
-                    *background,
-                    line_height,
-                    char_width,
-                    &mut cur_line_x,
-                );
+    // when the complexity of the whole file determines its background, its lines are highlighted before drawing
+    // them, while otherwise each line is highlighted as it is drawn.
+    let mut highlighted = Vec::new();
+    let file_color = match bg_color {
+        BgColor::Complexity => {
+            let mut complexity = Complexity::default();
+            let decisions_before = highlighter.decisions;
+            for line in content.as_bytes().lines_with_terminator() {
+                let (line, _, _) = split_line(line, column_width, highlight_truncated_lines);
+                complexity.add_line(line, tab_spaces);
+                highlighted.push(highlight(highlighter, line, ss)?);
             }
+            complexity.decisions = highlighter.decisions - decisions_before;
+            Some(color::gradient(
+                &color::HEAT_GRADIENT,
+                complexity.position(),
+            ))
         }
+        _ => file_color,
+    };
+    let mut highlighted = highlighted.into_iter();
 
+    // write the filename
+    if show_filenames {
+        write_label(
+            filepath.to_str().unwrap(),
+            img,
+            &mut unifont,
+            Label {
+                line_num,
+                total_line_count,
+                lines_per_column,
+                column_width,
+                char_width,
+                line_height,
+                readable,
+                background: bg_color.to_rgb(style, file_index, color_modulation, file_color),
+                foreground: initial_forground_color,
+            },
+        );
         line_num += 1;
     }
 
     // render all lines in `content` to image
-    let mut longest_line_in_chars = 0;
     let mut background = None::<Rgb<u8>>;
+    let mut line_bracket_depth = 0;
+    let overlay_lines: Vec<_> = line_overlays
+        .iter()
+        .filter_map(|overlay| overlay.lines(overlay_root, relative_path))
+        .collect();
+    let depth_color = |depth: u32| {
+        color::gradient(
+            depth_gradient,
+            depth as f32 / gradient_max_depth.max(1) as f32,
+        )
+    };
+    let mut longest_line_in_chars = 0;
+    let marker_line_num = content.lines().count();
     for (file_line_num, line) in content.as_bytes().lines_with_terminator().enumerate() {
         // make file_line_num that of the file.
         let file_line_num = file_line_num + 1;
 
-        let (line, truncated_line) = {
-            let line = line.to_str().expect("UTF-8 was source");
-            let mut num_chars = 0;
-            let mut chars = line.chars();
-            let bytes_till_char_limit: usize = chars
-                .by_ref()
-                .take(column_width as usize)
-                .map(|c| {
-                    num_chars += 1;
-                    c.len_utf8()
-                })
-                .sum();
-            num_chars += chars.count();
-            longest_line_in_chars = longest_line_in_chars.max(num_chars);
-            let possibly_truncated_line = (num_chars >= column_width as usize)
-                .then(|| &line[..bytes_till_char_limit])
-                .unwrap_or(line);
-            (
-                if highlight_truncated_lines {
-                    possibly_truncated_line
-                } else {
-                    line
-                },
-                possibly_truncated_line,
-            )
-        };
+        let (line, truncated_line, num_chars) =
+            split_line(line, column_width, highlight_truncated_lines);
+        longest_line_in_chars = longest_line_in_chars.max(num_chars);
 
         let actual_line = line_num % total_line_count;
         let (cur_column_x_offset, cur_y) = calc_offsets(
@@ -228,19 +292,31 @@ where
             column_width * char_width,
             line_height,
         );
-        let storage;
+        let storage = match highlighted.next() {
+            Some(regions) => regions,
+            None => highlight(highlighter, line, ss)?,
+        };
         let array_storage;
+        let regions: &[_] = match &storage {
+            Some(regions) => regions,
+            None => {
+                array_storage = [(default_bg_color(background), truncated_line)];
+                &array_storage
+            }
+        };
+        let background = background.get_or_insert_with(|| {
+            bg_color.to_rgb(regions[0].0, file_index, color_modulation, file_color)
+        });
+        let mut cur_line_x = 0;
 
-        let regions: &[_] = if line.len() > 1024 * 16 {
-            array_storage = [(default_bg_color(background), truncated_line)];
-            &array_storage
+        // earlier overlays take precedence over later ones, and the truncation marker over all of them.
+        let overlay_tint = if truncated && file_line_num == marker_line_num {
+            Some(color::TRUNCATED)
         } else {
-            storage = highlight(line)?;
-            &storage
+            overlay_lines
+                .iter()
+                .find_map(|lines| lines.get(file_line_num as u32))
         };
-        let background = background
-            .get_or_insert_with(|| bg_color.to_rgb(regions[0].0, file_index, color_modulation));
-        let mut cur_line_x = 0;
 
         // draw file_line_num for this line
         if line_nums {
@@ -283,6 +359,11 @@ where
         }
 
         // Draw the line on the image.
+        let tints = LineTints::new(highlight_patterns, line, highlight_whole_lines);
+        let indentation_depth = indentation_columns(line, tab_spaces) / tab_spaces.max(1);
+        let mut bracket_depth = line_bracket_depth;
+        line_bracket_depth = bracket_depth_after(line, line_bracket_depth);
+        let mut byte_offset = 0;
         for (style, region) in regions {
             if cur_line_x >= column_width * char_width {
                 break;
@@ -295,6 +376,9 @@ where
                 if cur_line_x >= column_width * char_width {
                     break;
                 }
+                let tint = tints.at(byte_offset);
+                byte_offset += chr.len_utf8();
+                let background = &color::tint(color::tint(*background, overlay_tint), tint);
 
                 let char_color: Rgb<u8> = match fg_color {
                     FgColor::Style => {
@@ -315,7 +399,28 @@ where
                                 * 256.0) as u8,
                         ])
                     }
+                    FgColor::IndentationDepth => depth_color(indentation_depth),
+                    FgColor::BracketDepth => {
+                        let depth = bracket_depth;
+                        bracket_depth = bracket_depth_after_char(chr, bracket_depth);
+                        // closing brackets share the depth of their opening counterpart.
+                        depth_color(depth.min(bracket_depth))
+                    }
+                };
+                let char_color = match chr.to_digit(16) {
+                    Some(value) if hex_density => {
+                        color::blend(*background, char_color, value as f32 / 15.0)
+                    }
+                    _ => char_color,
+                };
+                let char_color = color::tint(color::tint(char_color, overlay_tint), tint);
+                let (background, char_color) = match replacement_color {
+                    Some(replacement) if chr == char::REPLACEMENT_CHARACTER => {
+                        (replacement, replacement)
+                    }
+                    _ => (*background, char_color),
                 };
+                let background = &background;
 
                 if chr == ' ' || chr == '\n' || chr == '\r' {
                     if readable {
@@ -403,6 +508,7 @@ where
         }
 
         // Fill the rest of the line with the background color.
+        let background = &color::tint(color::tint(*background, overlay_tint), tints.line());
         if readable {
             while cur_line_x < column_width {
                 put_readable_char_in_image(
@@ -435,12 +541,136 @@ where
         line_num += 1;
     }
 
+    if let Some(dim) = dim {
+        // fade every pixel of the file into its background, including filenames and line numbers.
+        let file_background = bg_color.to_rgb(style, file_index, color_modulation, file_color);
+        for line_num in first_line_num..line_num {
+            let (x_offset, y_offset) = calc_offsets(
+                line_num % total_line_count,
+                lines_per_column,
+                column_width * char_width,
+                line_height,
+            );
+            for y in y_offset..y_offset + line_height {
+                for x in x_offset..x_offset + column_width * char_width {
+                    let pixel = img.get_pixel_mut(x, y);
+                    *pixel = color::blend(color::desaturate(*pixel, dim), file_background, dim);
+                }
+            }
+        }
+    }
+
     Ok(Outcome {
         longest_line_in_chars,
         background,
     })
 }
 
+/// Where and how to write a label, like the name of a file, into a line of an image.
+pub(crate) struct Label {
+    pub line_num: u32,
+    pub total_line_count: u32,
+    pub lines_per_column: u32,
+    pub column_width: u32,
+    pub char_width: u32,
+    pub line_height: u32,
+    pub readable: bool,
+    pub background: Rgb<u8>,
+    pub foreground: Rgb<u8>,
+}
+
+/// Write `text` into the line of the image given by `label`, and fill the rest of the line with its background.
+pub(crate) fn write_label<C>(
+    text: &str,
+    img: &mut ImageBuffer<Rgb<u8>, C>,
+    unifont: &mut Unifont,
+    Label {
+        line_num,
+        total_line_count,
+        lines_per_column,
+        column_width,
+        char_width,
+        line_height,
+        readable,
+        background,
+        foreground,
+    }: Label,
+) where
+    C: Deref<Target = [u8]>,
+    C: DerefMut,
+{
+    let background = &background;
+    // figure out where in the image to write
+    let actual_line = line_num % total_line_count;
+    let (cur_column_x_offset, cur_y) = calc_offsets(
+        actual_line,
+        lines_per_column,
+        column_width * char_width,
+        line_height,
+    );
+
+    // write the text on image
+    // let char_color = Rgb([255, 255, 255]);
+    let mut cur_line_x = 0;
+    for chr in text.chars() {
+        if readable {
+            put_readable_char_in_image(
+                chr,
+                unifont,
+                cur_column_x_offset + cur_line_x * char_width,
+                cur_y,
+                img,
+                background,
+                &foreground,
+                &mut cur_line_x,
+            );
+        } else {
+            // Fill the char space with a solid color.
+            let img_x = cur_column_x_offset + cur_line_x;
+            put_solid_char_in_image(
+                img_x,
+                cur_y,
+                img,
+                foreground,
+                line_height,
+                char_width,
+                &mut cur_line_x,
+            );
+        }
+    }
+
+    // Fill the rest of the line with the background color.
+    if readable {
+        while cur_line_x < column_width {
+            put_readable_char_in_image(
+                ' ',
+                unifont,
+                cur_column_x_offset + cur_line_x * char_width,
+                cur_y,
+                img,
+                background,
+                background,
+                &mut cur_line_x,
+            );
+        }
+    } else {
+        while cur_line_x < column_width * char_width {
+            // Fill the char space with a solid color.
+            let img_x = cur_column_x_offset + cur_line_x;
+            put_solid_char_in_image(
+                img_x,
+                cur_y,
+                img,
+                *background,
+                line_height,
+                char_width,
+                &mut cur_line_x,
+            );
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn put_readable_char_in_image<C>(
     chr: char,
     unifont: &mut Unifont,
@@ -453,6 +683,37 @@ fn put_readable_char_in_image<C>(
 ) where
     C: Deref<Target = [u8]>,
     C: DerefMut,
+{
+    let glyph_width = put_scaled_char_in_image(
+        chr,
+        unifont,
+        img_x,
+        img_y,
+        1,
+        img,
+        background_color,
+        text_color,
+    );
+    *cur_line_x += glyph_width / 8;
+}
+
+/// Draw the unifont glyph of `chr` with each of its pixels blown up to a `scale` x `scale` square.
+///
+/// Returns the width of the drawn glyph in pixels. Pixels outside of the image are skipped.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn put_scaled_char_in_image<C>(
+    chr: char,
+    unifont: &mut Unifont,
+    img_x: u32,
+    img_y: u32,
+    scale: u32,
+    img: &mut ImageBuffer<Rgb<u8>, C>,
+    background_color: &Rgb<u8>,
+    text_color: &Rgb<u8>,
+) -> u32
+where
+    C: Deref<Target = [u8]>,
+    C: DerefMut,
 {
     let bitmap = unifont.load_bitmap(chr.into());
 
@@ -464,40 +725,34 @@ fn put_readable_char_in_image<C>(
     // add bitmap to image
     for y in 0..char_height as usize {
         for x in 0..char_width {
-            let pixel_x = img_x + x;
-            let pixel_y = img_y + y as u32;
-
             // get pixel from bitmap
             let should_pixel = if bitmap.is_wide() {
                 bitmap.get_bytes()[y * 2 + x as usize / 8] & (1 << (7 - x % 8)) != 0
             } else {
                 bitmap.get_bytes()[y] & (1 << (7 - x)) != 0
             };
-
-            // if not in image bounds
-            if pixel_x >= img.width() || pixel_y >= img.height() {
-                // println!(
-                //     "Skipping pixel. out of bounds: {}, {}",
-                //     img_x + x,
-                //     img_y + y as u32
-                // );
-                continue;
+            let color = if should_pixel {
+                text_color
             } else {
-                // set pixel in image
-                if should_pixel {
-                    img.put_pixel(pixel_x, pixel_y, *text_color);
-                } else {
-                    img.put_pixel(pixel_x, pixel_y, *background_color);
+                background_color
+            };
+
+            for scaled_y in 0..scale {
+                for scaled_x in 0..scale {
+                    let pixel_x = img_x + x * scale + scaled_x;
+                    let pixel_y = img_y + y as u32 * scale + scaled_y;
+
+                    // if not in image bounds
+                    if pixel_x >= img.width() || pixel_y >= img.height() {
+                        continue;
+                    }
+                    img.put_pixel(pixel_x, pixel_y, *color);
                 }
             }
         }
     }
 
-    if bitmap.is_wide() {
-        *cur_line_x += 2;
-    } else {
-        *cur_line_x += 1;
-    }
+    char_width * scale
 }
 
 /// Fill the char space with a solid color.
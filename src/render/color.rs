@@ -0,0 +1,107 @@
+use anyhow::{bail, Context};
+use image::Rgb;
+
+/// How strongly tints, like the colors of matched patterns or line overlays, are mixed into the colors they apply to.
+const TINT_RATIO: f32 = 0.65;
+
+/// Distinct colors to hand out to things that don't have a color configured.
+pub const PALETTE: [Rgb<u8>; 8] = [
+    Rgb([230, 25, 75]),
+    Rgb([255, 225, 25]),
+    Rgb([60, 180, 75]),
+    Rgb([67, 99, 216]),
+    Rgb([245, 130, 49]),
+    Rgb([145, 30, 180]),
+    Rgb([66, 212, 244]),
+    Rgb([240, 50, 230]),
+];
+
+/// Green to yellow to red, for values where higher is worse.
+pub const HEAT_GRADIENT: [Rgb<u8>; 3] =
+    [Rgb([60, 180, 75]), Rgb([255, 225, 25]), Rgb([230, 25, 75])];
+
+/// Dark red to orange to yellow, like the colors of flames, for values where higher is hotter.
+pub const FLAME_GRADIENT: [Rgb<u8>; 3] =
+    [Rgb([128, 0, 0]), Rgb([245, 130, 49]), Rgb([255, 225, 25])];
+
+/// The tint of the marker line at the end of truncated files.
+pub const TRUNCATED: Rgb<u8> = Rgb([230, 25, 75]);
+
+/// Return the color at `index` of the palette, continuing with generated colors once the [`PALETTE`] is used up,
+/// so that each index gets its own color.
+pub fn palette(index: usize) -> Rgb<u8> {
+    match PALETTE.get(index) {
+        Some(color) => *color,
+        None => {
+            // stepping by the golden angle spreads hues evenly no matter how many are needed,
+            // while alternating the brightness keeps similar hues apart.
+            let step = index - PALETTE.len();
+            let hue = (15.0 + step as f32 * 137.507_76) % 360.0;
+            let value = if step.is_multiple_of(2) { 0.9 } else { 0.65 };
+            from_hsv(hue, 0.75, value)
+        }
+    }
+}
+
+/// Convert a `hue` in degrees, along with a `saturation` and `value` from `0.0` to `1.0`, to a color.
+fn from_hsv(hue: f32, saturation: f32, value: f32) -> Rgb<u8> {
+    let chroma = value * saturation;
+    let sector = hue / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    let channel = |c: f32| ((c + m) * 255.0).round() as u8;
+    Rgb([channel(r), channel(g), channel(b)])
+}
+
+/// Parse a hex color like `#ff8800` or `ff8800`.
+pub fn parse(color: &str) -> anyhow::Result<Rgb<u8>> {
+    let hex = color.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        bail!("Expected a hex color like '#ff8800', got {color:?}")
+    }
+    let channel = |range: std::ops::Range<usize>| {
+        u8::from_str_radix(&hex[range], 16)
+            .with_context(|| format!("Invalid hex digits in color {color:?}"))
+    };
+    Ok(Rgb([channel(0..2)?, channel(2..4)?, channel(4..6)?]))
+}
+
+/// Mix `a` and `b`, where a `ratio` of `0.0` yields `a` and `1.0` yields `b`.
+pub fn blend(a: Rgb<u8>, b: Rgb<u8>, ratio: f32) -> Rgb<u8> {
+    let ratio = ratio.clamp(0.0, 1.0);
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * ratio).round() as u8;
+    Rgb([mix(a[0], b[0]), mix(a[1], b[1]), mix(a[2], b[2])])
+}
+
+/// Remove the given `amount` of saturation from `color`, where `1.0` turns it into a shade of gray.
+pub fn desaturate(color: Rgb<u8>, amount: f32) -> Rgb<u8> {
+    let [r, g, b] = color.0;
+    let luma = (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32).round() as u8;
+    blend(color, Rgb([luma, luma, luma]), amount)
+}
+
+/// Pick the color at `position` along evenly spaced `stops`, where `0.0` is the first and `1.0` the last stop.
+pub fn gradient(stops: &[Rgb<u8>], position: f32) -> Rgb<u8> {
+    match stops {
+        [] => Rgb([255, 255, 255]),
+        [only] => *only,
+        _ => {
+            let scaled = position.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+            let index = (scaled.floor() as usize).min(stops.len() - 2);
+            blend(stops[index], stops[index + 1], scaled - index as f32)
+        }
+    }
+}
+
+/// Apply the given `tint`, if any, to `color`.
+pub fn tint(color: Rgb<u8>, tint: Option<Rgb<u8>>) -> Rgb<u8> {
+    tint.map_or(color, |tint| blend(color, tint, TINT_RATIO))
+}
@@ -0,0 +1,48 @@
+use crate::render::chunk::indentation_columns;
+use std::str::FromStr;
+use syntect::highlighting::ScopeSelectors;
+
+/// The scopes of tokens which branch or loop, excluding imports which some syntaxes put into `keyword.control`.
+const DECISION_SCOPES: &str =
+    "keyword.control - keyword.control.import, keyword.operator.logical, keyword.operator.ternary";
+
+/// The score at which files get the last color of the gradient.
+pub(crate) const MAX_SCORE: f32 = 100.0;
+
+/// Return the selectors matching the scopes of tokens which branch or loop.
+pub(crate) fn decision_scopes() -> ScopeSelectors {
+    ScopeSelectors::from_str(DECISION_SCOPES).expect("valid scope selectors")
+}
+
+/// A lightweight estimate of how hard a file is to understand.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Complexity {
+    /// The amount of control flow keywords and logical operators.
+    pub decisions: u32,
+    /// The deepest indentation level of any line.
+    pub max_nesting: u32,
+    /// The amount of lines in the file.
+    pub lines: u32,
+}
+
+impl Complexity {
+    /// Account for `line`, a line of the file, in the nesting and amount of lines.
+    pub fn add_line(&mut self, line: &str, tab_spaces: u32) {
+        self.lines += 1;
+        self.max_nesting = self
+            .max_nesting
+            .max(indentation_columns(line, tab_spaces) / tab_spaces.max(1));
+    }
+
+    /// Combine all measurements into a single number, where higher is more complex.
+    ///
+    /// Each decision counts once, each level of the deepest nesting twice and every 50 lines once.
+    pub fn score(&self) -> f32 {
+        self.decisions as f32 + 2.0 * self.max_nesting as f32 + self.lines as f32 / 50.0
+    }
+
+    /// Return the position of the score on a gradient from `0.0` to `1.0`, which is reached at [`MAX_SCORE`].
+    pub fn position(&self) -> f32 {
+        (self.score() / MAX_SCORE).min(1.0)
+    }
+}
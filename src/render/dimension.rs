@@ -55,19 +55,21 @@ pub(crate) fn compute(
 
                 // determine required number of columns
                 required_columns = total_line_count / lines_per_column;
-                if total_line_count % lines_per_column != 0 {
+                if !total_line_count.is_multiple_of(lines_per_column) {
                     required_columns += 1;
                 }
 
                 let last_required_columns = required_columns;
 
-                // find next full column aspect ratio
-                while required_columns == last_required_columns {
+                // find next full column aspect ratio, unless everything already fits into one column
+                while required_columns == last_required_columns
+                    && lines_per_column < total_line_count
+                {
                     lines_per_column += 1;
 
                     // determine required number of columns
                     required_columns = total_line_count / lines_per_column;
-                    if total_line_count % lines_per_column != 0 {
+                    if !total_line_count.is_multiple_of(lines_per_column) {
                         required_columns += 1;
                     }
                 }
@@ -78,7 +80,7 @@ pub(crate) fn compute(
 
                 // determine required number of columns
                 required_columns = total_line_count / lines_per_column;
-                if total_line_count % lines_per_column != 0 {
+                if !total_line_count.is_multiple_of(lines_per_column) {
                     required_columns += 1;
                 }
             }
@@ -101,7 +103,7 @@ pub(crate) fn compute(
 
         // determine required number of columns
         required_columns = total_line_count / lines_per_column;
-        if total_line_count % lines_per_column != 0 {
+        if !total_line_count.is_multiple_of(lines_per_column) {
             required_columns += 1;
         }
     }
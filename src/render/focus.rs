@@ -0,0 +1,59 @@
+use anyhow::Context;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::{Component, Path, PathBuf};
+
+/// Select the files to show at full brightness, while all other files are dimmed.
+#[derive(Debug, Clone)]
+pub struct Focus {
+    globs: GlobSet,
+    paths: Vec<PathBuf>,
+    /// How strongly files out of focus are dimmed, from `0.0` (not at all) to `1.0` (invisible).
+    pub dim: f32,
+}
+
+impl Focus {
+    /// Create a new instance to focus on files matching any of the `globs`, or any of the `paths`
+    /// or lying in any of the `paths` if they are directories.
+    ///
+    /// All globs and paths are relative to the rendered directory.
+    pub fn new(globs: &[String], paths: Vec<PathBuf>, dim: f32) -> anyhow::Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        for glob in globs {
+            builder.add(Glob::new(glob).with_context(|| format!("Invalid focus glob {glob:?}"))?);
+        }
+        Ok(Focus {
+            globs: builder.build()?,
+            paths: paths
+                .into_iter()
+                .map(|path| {
+                    path.components()
+                        .filter(|component| component != &Component::CurDir)
+                        .collect()
+                })
+                .collect(),
+            dim,
+        })
+    }
+
+    /// Read newline separated paths to focus on from the file at `list`, in addition to `globs`.
+    /// Empty lines and lines starting with `#` are ignored.
+    pub fn from_list(list: &Path, globs: &[String], dim: f32) -> anyhow::Result<Self> {
+        let paths = std::fs::read_to_string(list)
+            .with_context(|| format!("Failed to read focus list at {list:?}"))?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(PathBuf::from)
+            .collect();
+        Focus::new(globs, paths, dim)
+    }
+
+    /// Return true if the file at `relative_path` is in focus.
+    pub fn is_match(&self, relative_path: &Path) -> bool {
+        self.globs.is_match(relative_path)
+            || self
+                .paths
+                .iter()
+                .any(|path| relative_path.starts_with(path))
+    }
+}
@@ -1,13 +1,16 @@
+use crate::overlay::RootPaths;
 use crate::render::chunk::calc_offsets;
 use crate::render::Cache;
 use crate::render::Dimension;
-use crate::render::{chunk, Options};
+use crate::render::{caption, chunk, color, complexity, BgColor, Options};
 use crate::DirContents;
 use crate::FILENAME_LINE_COUNT;
 use anyhow::{bail, Context};
 use image::{ImageBuffer, Pixel, Rgb, RgbImage};
 use memmap2::MmapMut;
 use prodash::Progress;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use syntect::highlighting::ThemeSet;
 use syntect::parsing::SyntaxSet;
@@ -37,6 +40,17 @@ pub fn render(
         color_modulation,
         tab_spaces,
         line_nums,
+        depth_gradient,
+        gradient_max_depth,
+        caption,
+        highlight_patterns,
+        highlight_whole_lines,
+        focus,
+        line_overlays,
+        code_owners,
+        replacement_color,
+        dim_generated,
+        separate_roots,
     }: Options,
 ) -> anyhow::Result<ImageBuffer<Rgb<u8>, MmapMut>> {
     // unused for now
@@ -52,20 +66,20 @@ pub fn render(
     let line_height = line_height;
     let char_width = char_width;
 
-    //> read files (for /n counting)
-    let (content, total_line_count, num_ignored) = {
+    //> count lines of files, whose content is only loaded once they are rendered
+    let (mut content, total_line_count, num_ignored) = {
         let mut out = Vec::with_capacity(dir_content.children_content.len());
         let mut lines = 0;
         let mut num_ignored = 0;
         let mut lines_so_far = 0u32;
-        for (path, content) in &dir_content.children_content {
-            let num_content_lines = content.lines().count();
+        for file in &dir_content.children_content {
+            let (path, num_content_lines) = (&file.path, file.lines);
             lines += num_content_lines;
-            if ignore_files_without_syntax && ss.find_syntax_for_file(path)?.is_none() {
+            if ignore_files_without_syntax && file.syntax(ss)?.is_none() {
                 lines -= num_content_lines;
                 num_ignored += 1;
             } else {
-                out.push(((path, content), num_content_lines, lines_so_far));
+                out.push(((path, file), num_content_lines, lines_so_far));
                 lines_so_far += num_content_lines as u32;
                 if show_filenames {
                     lines_so_far += FILENAME_LINE_COUNT;
@@ -74,6 +88,95 @@ pub fn render(
         }
         (out, lines as u32, num_ignored)
     };
+    let total_file_count = content.len();
+
+    // determine the colors of files for background modes which color each file individually,
+    // along with the legend for these colors.
+    let (file_colors, file_legend): (Vec<_>, Vec<_>) = match bg_color {
+        BgColor::Complexity => {
+            // the color of each file is determined from its complexity while highlighting it.
+            let steps = 4;
+            let legend = (0..=steps)
+                .map(|step| {
+                    let position = step as f32 / steps as f32;
+                    let score = complexity::MAX_SCORE * position;
+                    (
+                        Some(color::gradient(&color::HEAT_GRADIENT, position)),
+                        if step == steps {
+                            format!("complexity {score:.0}+")
+                        } else {
+                            format!("complexity {score:.0}")
+                        },
+                    )
+                })
+                .collect();
+            (vec![None; content.len()], legend)
+        }
+        BgColor::Owner => {
+            if code_owners.iter().all(Option::is_none) {
+                bail!(
+                    "Coloring backgrounds by owner requires a CODEOWNERS file, but none was found"
+                )
+            }
+            let owners: Vec<_> = content
+                .iter()
+                .map(|((_, file), _, _)| {
+                    let code_owners = code_owners.get(file.root)?.as_ref()?;
+                    code_owners
+                        .owners_of(dir_content.relative_path(file))
+                        .first()
+                })
+                .collect();
+
+            // give the owners with the most lines the most distinct colors.
+            let mut line_totals = BTreeMap::<Option<&String>, usize>::new();
+            for (owner, (_, num_content_lines, _)) in owners.iter().zip(&content) {
+                *line_totals.entry(*owner).or_default() += num_content_lines;
+            }
+            let mut line_totals: Vec<_> = line_totals.into_iter().collect();
+            line_totals.sort_by_key(|(owner, lines)| (owner.is_none(), Reverse(*lines)));
+            let owner_colors: HashMap<_, _> = line_totals
+                .iter()
+                .filter_map(|(owner, _)| *owner)
+                .enumerate()
+                .map(|(index, owner)| (owner, color::palette(index)))
+                .collect();
+
+            let file_colors = owners
+                .iter()
+                .map(|owner| owner.map(|owner| owner_colors[owner]))
+                .collect();
+            let legend = line_totals
+                .into_iter()
+                .map(|(owner, lines)| {
+                    (
+                        owner.map(|owner| owner_colors[owner]),
+                        format!(
+                            "{} ({lines} lines)",
+                            owner.map_or("unowned", |o| o.as_str())
+                        ),
+                    )
+                })
+                .collect();
+            (file_colors, legend)
+        }
+        BgColor::Style
+        | BgColor::StyleCheckerboardDarken
+        | BgColor::StyleCheckerboardBrighten
+        | BgColor::HelixEditor => (vec![None; content.len()], Vec::new()),
+    };
+
+    // determine how much to dim each file, if at all.
+    let file_dims: Vec<_> = content
+        .iter()
+        .map(|((path, file), _, _)| {
+            focus
+                .filter(|focus| !focus.is_match(&dir_content.label(file)))
+                .map(|focus| focus.dim)
+                .or_else(|| dim_generated.filter(|_| dir_content.generated.contains(*path)))
+        })
+        .collect();
+    let content_line_count = total_line_count;
 
     if total_line_count == 0 {
         bail!(
@@ -87,15 +190,13 @@ pub fn render(
     if show_filenames {
         total_line_count += content.len() as u32 * FILENAME_LINE_COUNT;
     }
-    // re-make immutable
-    let total_line_count = total_line_count;
 
     // determine number and height of columns closest to desired aspect ratio
     let Dimension {
-        imgx,
+        mut imgx,
         imgy,
         lines_per_column,
-        required_columns,
+        mut required_columns,
     } = crate::render::dimension::compute(
         target_aspect_ratio,
         column_width * char_width,
@@ -105,6 +206,37 @@ pub fn render(
         progress.add_child("determine dimensions"),
     )?;
 
+    // start the files of each root in a new column, by leaving the rest of the previous column empty,
+    // and add columns for the lines this takes. Without file names, each root starts with a line showing its name.
+    let mut gaps = Vec::new();
+    let mut root_labels = Vec::new();
+    if separate_roots {
+        let mut line_num = 0;
+        let mut previous_root = None;
+        for ((_, file), num_content_lines, lines_so_far) in &mut content {
+            let remainder = line_num % lines_per_column;
+            if previous_root.is_some_and(|root| root != file.root) && remainder != 0 {
+                gaps.push(line_num..line_num + lines_per_column - remainder);
+                line_num += lines_per_column - remainder;
+            }
+            if !show_filenames && previous_root != Some(file.root) {
+                root_labels.push((line_num, file.root));
+                line_num += FILENAME_LINE_COUNT;
+            }
+            previous_root = Some(file.root);
+            *lines_so_far = line_num;
+            line_num += *num_content_lines as u32;
+            if show_filenames {
+                line_num += FILENAME_LINE_COUNT;
+            }
+        }
+        total_line_count = line_num;
+        required_columns = total_line_count.div_ceil(lines_per_column);
+        imgx = required_columns * column_width * char_width;
+    }
+    // re-make immutable
+    let total_line_count = total_line_count;
+
     let num_pixels = {
         let channel_count = Rgb::<u8>::CHANNEL_COUNT;
         let num_pixels = imgx as usize * imgy as usize * channel_count as usize;
@@ -130,25 +262,29 @@ pub fn render(
         prodash::unit::label_and_mode("lines", prodash::unit::display::Mode::with_throughput())
             .into(),
     );
-    let mut cache = Cache::new_with_plain_highlighter(
-        ss,
-        ts.themes.get(theme).with_context(|| {
-            format!(
-                "Could not find theme {theme:?}, must be one of {}",
-                ts.themes
-                    .keys()
-                    .map(|s| format!("{s:?}"))
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            )
-        })?,
-    );
+    let theme_name = theme;
+    let theme = ts.themes.get(theme_name).with_context(|| {
+        format!(
+            "Could not find theme {theme_name:?}, must be one of {}",
+            ts.themes
+                .keys()
+                .map(|s| format!("{s:?}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })?;
+    let mut cache = Cache::new_with_plain_highlighter(ss, theme, bg_color == BgColor::Complexity);
+    let overlay_roots: Vec<_> = dir_content
+        .roots
+        .iter()
+        .map(|root| RootPaths::new(&root.dir))
+        .collect();
 
     let threads = (threads == 0)
         .then(num_cpus::get)
         .unwrap_or(threads)
         .clamp(1, num_cpus::get());
-    let (mut line_num, longest_line_chars, background) = if threads < 2 {
+    let (line_num, longest_line_chars, background) = if threads < 2 {
         // single-threaded rendering overview:
         //
         // Sqeuentially iterates over the contents of each file to render.
@@ -159,28 +295,31 @@ pub fn render(
         let mut longest_line_chars = 0;
         let mut background = None;
         let mut highlighter = cache.new_plain_highlighter();
-        for (file_index, ((path, content), num_content_lines, _lines_so_far)) in
+        for (file_index, ((path, file), num_content_lines, lines_so_far)) in
             content.into_iter().enumerate()
         {
+            line_num = lines_so_far;
             progress.inc();
             if should_interrupt.load(Ordering::Relaxed) {
                 bail!("Cancelled by user")
             }
+            let content = file.content()?;
             if !plain {
-                if let Some(hl) = cache.highlighter_for_file_name(path)? {
+                if let Some(hl) = cache.highlighter_for_file(file)? {
                     highlighter = hl;
                 }
             }
 
-            let relative_path = path.strip_prefix(&dir_content.parent_dir).unwrap();
+            let label = dir_content.label(file);
             if display_to_be_processed_file {
-                progress.info(format!("{relative_path:?}"))
+                progress.info(format!("{label:?}"))
             }
             let out = chunk::process(
-                &relative_path,
-                content,
+                &label,
+                &content,
                 &mut img,
-                |line| highlighter.highlight_line(line, ss),
+                &mut highlighter,
+                ss,
                 chunk::Context {
                     column_width,
                     line_height,
@@ -192,11 +331,23 @@ pub fn render(
                     fg_color,
                     bg_color,
                     file_index,
+                    file_color: file_colors[file_index],
                     color_modulation,
                     tab_spaces,
                     readable,
                     show_filenames,
                     line_nums,
+                    depth_gradient,
+                    gradient_max_depth,
+                    highlight_patterns,
+                    highlight_whole_lines,
+                    line_overlays,
+                    overlay_root: &overlay_roots[file.root],
+                    relative_path: dir_content.relative_path(file),
+                    replacement_color,
+                    truncated: dir_content.truncated.contains(path),
+                    hex_density: dir_content.binary.contains(path),
+                    dim: file_dims[file_index],
                 },
             )?;
             longest_line_chars = out.longest_line_in_chars.max(longest_line_chars);
@@ -235,6 +386,9 @@ pub fn render(
                     let file_index = &file_index;
                     let ss = &ss;
                     let content = &content;
+                    let file_colors = &file_colors;
+                    let file_dims = &file_dims;
+                    let overlay_roots = &overlay_roots;
                     let mut state = cache.clone();
                     let mut progress = line_progress.add_child(format!("Thread {tid}"));
                     move || -> anyhow::Result<()> {
@@ -244,10 +398,11 @@ pub fn render(
                                 (x < content.len()).then_some(x + 1)
                             })
                         {
-                            let ((path, content), num_content_lines, lines_so_far) =
+                            let ((path, file), num_content_lines, lines_so_far) =
                                 &content[file_index];
+                            let content = file.content()?;
                             if !plain {
-                                if let Some(hl) = state.highlighter_for_file_name(path)? {
+                                if let Some(hl) = state.highlighter_for_file(file)? {
                                     highlighter = hl;
                                 }
                             }
@@ -262,15 +417,16 @@ pub fn render(
                             // create an image that fits one column
                             let mut img = RgbImage::new(column_width * char_width, img_height);
 
-                            let relative_path = path.strip_prefix(&dir_content.parent_dir).unwrap();
+                            let label = dir_content.label(file);
                             if display_to_be_processed_file {
-                                progress.info(format!("{relative_path:?}"))
+                                progress.info(format!("{label:?}"))
                             }
                             let out = chunk::process(
-                                &relative_path,
-                                content,
+                                &label,
+                                &content,
                                 &mut img,
-                                |line| highlighter.highlight_line(line, ss),
+                                &mut highlighter,
+                                ss,
                                 chunk::Context {
                                     column_width,
                                     line_height,
@@ -282,11 +438,23 @@ pub fn render(
                                     fg_color,
                                     bg_color,
                                     file_index,
+                                    file_color: file_colors[file_index],
                                     color_modulation,
                                     tab_spaces,
                                     readable,
                                     show_filenames,
                                     line_nums,
+                                    depth_gradient,
+                                    gradient_max_depth,
+                                    highlight_patterns,
+                                    highlight_whole_lines,
+                                    line_overlays,
+                                    overlay_root: &overlay_roots[file.root],
+                                    relative_path: dir_content.relative_path(file),
+                                    replacement_color,
+                                    truncated: dir_content.truncated.contains(*path),
+                                    hex_density: dir_content.binary.contains(*path),
+                                    dim: file_dims[file_index],
                                 },
                             )?;
                             ttx.send((img, out, *num_content_lines, *lines_so_far))?;
@@ -324,10 +492,7 @@ pub fn render(
                 }
 
                 line_progress.inc_by(num_content_lines);
-                line_num += num_content_lines as u32;
-                if show_filenames {
-                    line_num += FILENAME_LINE_COUNT
-                };
+                line_num = line_num.max(lines_so_far + lines_in_sub_img);
                 progress.inc();
                 if should_interrupt.load(Ordering::Relaxed) {
                     bail!("Cancelled by user")
@@ -338,11 +503,15 @@ pub fn render(
         (line_num, longest_line_chars, background)
     };
 
-    // fill in any empty bottom right corner, with background color
-    while line_num < lines_per_column * required_columns {
+    // fill in the gaps between roots and any empty bottom right corner, with background color
+    let background = background.unwrap_or(Rgb([0, 0, 0]));
+    for line_num in gaps
+        .into_iter()
+        .flatten()
+        .chain(line_num..lines_per_column * required_columns)
+    {
         let (cur_column_x_offset, cur_y) =
             calc_offsets(line_num, lines_per_column, column_width, line_height);
-        let background = background.unwrap_or(Rgb([0, 0, 0]));
 
         for cur_line_x in 0..column_width * char_width {
             for y_pos in cur_y..cur_y + line_height {
@@ -353,7 +522,32 @@ pub fn render(
                 );
             }
         }
-        line_num += 1;
+    }
+
+    // name the region of each root, as there are no file names telling them apart.
+    let label_style = caption::default_style(theme);
+    let mut unifont = unifont_bitmap::Unifont::open();
+    for (line_num, root) in root_labels {
+        chunk::write_label(
+            &dir_content.roots[root].name,
+            &mut img,
+            &mut unifont,
+            chunk::Label {
+                line_num,
+                total_line_count,
+                lines_per_column,
+                column_width,
+                char_width,
+                line_height,
+                readable,
+                background,
+                foreground: Rgb([
+                    label_style.foreground.r,
+                    label_style.foreground.g,
+                    label_style.foreground.b,
+                ]),
+            },
+        );
     }
 
     progress.show_throughput(start);
@@ -365,5 +559,44 @@ pub fn render(
         progress.info(format!("Ignored {num_ignored} files due to missing syntax",))
     }
 
+    if let Some(caption) = caption {
+        let mut legend = caption::legend(
+            fg_color,
+            bg_color,
+            theme,
+            color_modulation,
+            depth_gradient,
+            gradient_max_depth,
+        );
+        let default_style = caption::default_style(theme);
+        legend.extend(file_legend.into_iter().map(|(file_color, label)| {
+            (
+                bg_color.to_rgb(default_style, 0, color_modulation, file_color),
+                label,
+            )
+        }));
+        for overlay in line_overlays {
+            legend.extend(overlay.legend.iter().cloned());
+        }
+        legend.extend(
+            highlight_patterns
+                .iter()
+                .map(|pattern| (pattern.color, pattern.regex.as_str().to_owned())),
+        );
+        img = caption::append(
+            img,
+            caption,
+            caption::Stats {
+                theme: theme_name,
+                fg_color,
+                bg_color,
+                total_lines: content_line_count,
+                total_files: total_file_count,
+            },
+            &legend,
+            theme,
+        )?;
+    }
+
     Ok(img)
 }
@@ -1,9 +1,13 @@
-use std::path::Path;
+use crate::render::complexity;
+use syntect::highlighting::{HighlightIterator, HighlightState, ScopeSelectors, Style};
+use syntect::parsing::{ParseState, ScopeStack, ScopeStackOp, SyntaxReference, SyntaxSet};
 
 pub(crate) struct Cache<'syntax, 'theme> {
     syntax: &'syntax syntect::parsing::SyntaxSet,
     theme: &'theme syntect::highlighting::Theme,
     prev_syntax: usize,
+    /// If true, highlighters count the decisions in the lines they highlight.
+    count_decisions: bool,
 }
 
 impl<'a, 'b> Clone for Cache<'a, 'b> {
@@ -12,6 +16,7 @@ impl<'a, 'b> Clone for Cache<'a, 'b> {
             syntax: self.syntax,
             theme: self.theme,
             prev_syntax: self.prev_syntax,
+            count_decisions: self.count_decisions,
         }
     }
 }
@@ -20,35 +25,97 @@ impl<'syntax, 'theme> Cache<'syntax, 'theme> {
     pub fn new_with_plain_highlighter(
         syntax: &'syntax syntect::parsing::SyntaxSet,
         theme: &'theme syntect::highlighting::Theme,
+        count_decisions: bool,
     ) -> Self {
         let plain = syntax.find_syntax_plain_text();
         Cache {
             syntax,
             theme,
             prev_syntax: plain as *const _ as usize,
+            count_decisions,
         }
     }
 
-    pub fn new_plain_highlighter(&self) -> syntect::easy::HighlightLines<'theme> {
-        syntect::easy::HighlightLines::new(self.syntax.find_syntax_plain_text(), self.theme)
+    pub fn new_plain_highlighter(&self) -> Highlighter<'theme> {
+        Highlighter::new(
+            self.syntax.find_syntax_plain_text(),
+            self.theme,
+            self.count_decisions,
+        )
     }
 }
 
 impl<'syntax, 'theme> Cache<'syntax, 'theme> {
-    pub fn highlighter_for_file_name(
+    pub fn highlighter_for_file(
         &mut self,
-        path: &Path,
-    ) -> std::io::Result<Option<syntect::easy::HighlightLines<'theme>>> {
-        let syntax = self
-            .syntax
-            .find_syntax_for_file(path)
-            .unwrap()
+        file: &crate::content::File,
+    ) -> anyhow::Result<Option<Highlighter<'theme>>> {
+        let syntax = file
+            .syntax(self.syntax)?
             .unwrap_or_else(|| self.syntax.find_syntax_plain_text());
         if syntax as *const _ as usize != self.prev_syntax {
             self.prev_syntax = syntax as *const _ as usize;
-            Ok(Some(syntect::easy::HighlightLines::new(syntax, self.theme)))
+            Ok(Some(Highlighter::new(
+                syntax,
+                self.theme,
+                self.count_decisions,
+            )))
         } else {
             Ok(None)
         }
     }
 }
+
+/// Highlights lines like [`syntect::easy::HighlightLines`], while optionally counting the tokens which
+/// branch or loop to estimate the complexity of files without parsing them twice.
+pub(crate) struct Highlighter<'theme> {
+    highlighter: syntect::highlighting::Highlighter<'theme>,
+    parse_state: ParseState,
+    highlight_state: HighlightState,
+    /// The scopes of decisions, if they are counted.
+    decision_scopes: Option<ScopeSelectors>,
+    /// The amount of decisions in all highlighted lines.
+    pub decisions: u32,
+}
+
+impl<'theme> Highlighter<'theme> {
+    fn new(
+        syntax: &SyntaxReference,
+        theme: &'theme syntect::highlighting::Theme,
+        count_decisions: bool,
+    ) -> Self {
+        let highlighter = syntect::highlighting::Highlighter::new(theme);
+        let highlight_state = HighlightState::new(&highlighter, ScopeStack::new());
+        Highlighter {
+            highlighter,
+            parse_state: ParseState::new(syntax),
+            highlight_state,
+            decision_scopes: count_decisions.then(complexity::decision_scopes),
+            decisions: 0,
+        }
+    }
+
+    /// Highlight the next `line` of a file, which includes its line terminator.
+    pub fn highlight_line<'a>(
+        &mut self,
+        line: &'a str,
+        ss: &SyntaxSet,
+    ) -> Result<Vec<(Style, &'a str)>, syntect::Error> {
+        let ops = self.parse_state.parse_line(line, ss)?;
+        if let Some(decision_scopes) = &self.decision_scopes {
+            let mut scopes = self.highlight_state.path.clone();
+            for (_, op) in &ops {
+                scopes.apply(op)?;
+                if matches!(op, ScopeStackOp::Push(_))
+                    && decision_scopes.does_match(scopes.as_slice()).is_some()
+                {
+                    self.decisions += 1;
+                }
+            }
+        }
+        Ok(
+            HighlightIterator::new(&mut self.highlight_state, &ops, line, &self.highlighter)
+                .collect(),
+        )
+    }
+}
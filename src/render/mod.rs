@@ -8,8 +8,17 @@ pub enum FgColor {
     Style,
     /// Encode the ascii value into the brightness of the style color
     StyleAsciiBrightness,
+    /// Color characters along the depth gradient by the indentation of their line,
+    /// where each level is `tab_spaces` columns wide.
+    IndentationDepth,
+    /// Color characters along the depth gradient by how many brackets they are nested in.
+    /// Brackets in strings and comments are counted as well.
+    BracketDepth,
 }
 
+/// How strongly the colors assigned to individual files are mixed into the background color of the style.
+const FILE_COLOR_RATIO: f32 = 0.5;
+
 /// Determine the background pixel color.
 #[derive(clap::ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
 pub enum BgColor {
@@ -23,24 +32,48 @@ pub enum BgColor {
     StyleCheckerboardBrighten,
     /// The purple color of the Helix Editor.
     HelixEditor,
+    /// Mix the style of the syntax with a color from green to red by the estimated complexity of each file,
+    /// where files with a score of 100 or more are red.
+    ///
+    /// Complexity is estimated from the amount of control flow keywords and logical operators according
+    /// to the syntax, the deepest indentation level and the amount of lines. Each keyword or operator scores
+    /// one point, each level of the deepest indentation two points and every 50 lines one point.
+    /// With plain syntax highlighting, keywords and operators aren't known and thus don't count.
+    Complexity,
+    /// Mix the style of the syntax with a color for the first owner of each file according to the
+    /// `CODEOWNERS` file of the repository.
+    Owner,
 }
 
 impl BgColor {
-    pub fn to_rgb(&self, style: Style, file_index: usize, color_modulation: f32) -> Rgb<u8> {
+    /// Return the background color for the file at `file_index`, where `file_color` is the color assigned
+    /// to the file by modes which color files individually.
+    pub fn to_rgb(
+        &self,
+        style: Style,
+        file_index: usize,
+        color_modulation: f32,
+        file_color: Option<Rgb<u8>>,
+    ) -> Rgb<u8> {
+        let style_background = Rgb([style.background.r, style.background.g, style.background.b]);
         match self {
-            BgColor::Style => Rgb([style.background.r, style.background.g, style.background.b]),
+            BgColor::Style => style_background,
+            BgColor::Complexity | BgColor::Owner => file_color
+                .map_or(style_background, |file_color| {
+                    color::blend(style_background, file_color, FILE_COLOR_RATIO)
+                }),
             BgColor::HelixEditor => Rgb([59, 34, 76]),
             BgColor::StyleCheckerboardDarken | BgColor::StyleCheckerboardBrighten => {
                 let m = if self == &BgColor::StyleCheckerboardBrighten {
-                    if file_index % 2 == 0 {
+                    if file_index.is_multiple_of(2) {
                         1.0 + color_modulation
                     } else {
                         1.0
                     }
+                } else if file_index.is_multiple_of(2) {
+                    1.0
                 } else {
-                    (file_index % 2 == 0)
-                        .then_some(1.0)
-                        .unwrap_or_else(|| (1.0_f32 - color_modulation).max(0.0))
+                    (1.0_f32 - color_modulation).max(0.0)
                 };
                 Rgb([
                     (style.background.r as f32 * m).min(255.0) as u8,
@@ -86,6 +119,30 @@ pub struct Options<'a> {
     /// The number of spaces to use for a tab character.
     pub tab_spaces: u32,
     pub line_nums: bool,
+    /// The colors of the gradient used by the depth foreground colors, from shallow to deep.
+    pub depth_gradient: &'a [Rgb<u8>],
+    /// The nesting depth at which the last color of the `depth_gradient` is reached.
+    pub gradient_max_depth: u32,
+    /// The band with a title, statistics and a color legend to add to the image, if any.
+    pub caption: Option<Caption<'a>>,
+    /// Patterns whose matches are tinted with their color on top of the syntax colors.
+    pub highlight_patterns: &'a [search::Pattern],
+    /// Tint entire lines containing a match instead of only the matched text.
+    pub highlight_whole_lines: bool,
+    /// The files to keep at full brightness while dimming all others, if set.
+    pub focus: Option<&'a focus::Focus>,
+    /// Colors to mix into individual lines, like coverage, where earlier overlays take precedence.
+    pub line_overlays: &'a [crate::overlay::LineOverlay],
+    /// The owners of files in each root, by the index of the root, of which at least one is required
+    /// by the `Owner` background color.
+    pub code_owners: &'a [Option<crate::codeowners::CodeOwners>],
+    /// The color of characters replacing bytes which were invalid in the encoding of their file, if they should stand out.
+    pub replacement_color: Option<Rgb<u8>>,
+    /// How strongly to dim files which weren't written by hand, if at all.
+    pub dim_generated: Option<f32>,
+    /// Start the files of each root in a new column, so each root occupies its own region of the image.
+    /// Unless `show_filenames` is set, each region starts with a line showing the name of its root.
+    pub separate_roots: bool,
 }
 
 impl Default for Options<'_> {
@@ -108,10 +165,29 @@ impl Default for Options<'_> {
             color_modulation: 0.3,
             tab_spaces: 4,
             line_nums: false,
+            depth_gradient: &color::HEAT_GRADIENT,
+            gradient_max_depth: 8,
+            caption: None,
+            highlight_patterns: &[],
+            highlight_whole_lines: false,
+            focus: None,
+            line_overlays: &[],
+            code_owners: &[],
+            replacement_color: None,
+            dim_generated: None,
+            separate_roots: false,
         }
     }
 }
 
+pub mod caption;
+pub use caption::Caption;
+
+pub mod color;
+mod complexity;
+pub mod focus;
+pub mod search;
+
 mod highlight;
 use highlight::Cache;
 
@@ -0,0 +1,53 @@
+use image::Rgb;
+use std::ops::Range;
+
+/// A regular expression whose matches are tinted with a color on top of the syntax colors.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    pub regex: regex::Regex,
+    pub color: Rgb<u8>,
+}
+
+/// The tinted spans of a single line.
+pub(crate) struct LineTints {
+    /// Byte ranges into the line along with the color to tint them with.
+    spans: Vec<(Range<usize>, Rgb<u8>)>,
+    /// The color of the whole line, if it should be tinted entirely.
+    line: Option<Rgb<u8>>,
+}
+
+impl LineTints {
+    /// Match all `patterns` against `line`. Earlier patterns take precedence over later ones.
+    pub fn new(patterns: &[Pattern], line: &str, whole_lines: bool) -> Self {
+        let mut spans = Vec::new();
+        for pattern in patterns {
+            spans.extend(
+                pattern
+                    .regex
+                    .find_iter(line)
+                    .filter(|m| !m.range().is_empty())
+                    .map(|m| (m.range(), pattern.color)),
+            );
+        }
+        let line = whole_lines
+            .then(|| patterns.iter().find(|p| p.regex.is_match(line)))
+            .flatten()
+            .map(|p| p.color);
+        LineTints { spans, line }
+    }
+
+    /// Return the tint for the character at `byte_offset`, if any.
+    pub fn at(&self, byte_offset: usize) -> Option<Rgb<u8>> {
+        self.line.or_else(|| {
+            self.spans
+                .iter()
+                .find(|(range, _)| range.contains(&byte_offset))
+                .map(|(_, color)| *color)
+        })
+    }
+
+    /// Return the tint that applies to the space after the last character of the line.
+    pub fn line(&self) -> Option<Rgb<u8>> {
+        self.line
+    }
+}
@@ -0,0 +1,155 @@
+//! Strategies to order the files of an image.
+use crate::{content, DirContents};
+use anyhow::{bail, Context};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+use syntect::parsing::SyntaxSet;
+
+/// The order in which files are placed in the image.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Order {
+    /// Alphabetically by path, which keeps directories together.
+    Path,
+    /// Largest files first, by the length of their decoded content in bytes.
+    Size,
+    /// Longest files first, by their amount of lines.
+    Lines,
+    /// Grouped by the name of their syntax, with files without syntax last.
+    Language,
+    /// Most recently modified files first.
+    Modified,
+    /// Most frequently changed files first, by the amount of git commits touching them.
+    Churn,
+    /// In the order of a list of paths, with all files not in the list following by path.
+    List,
+    /// In the order the files were given in with `--files-from`, or found in while walking the input directory.
+    Given,
+}
+
+/// Sort the files of `dir_contents` by `order`, with ties broken by path, unless they keep the order they were given in.
+///
+/// Files stay grouped by their root, in the order of the roots, and are sorted within it.
+/// `ss` determines the syntax of files for `Order::Language`, and `list` is the file with one path
+/// per line, as [labeled](DirContents::label()), which is required for `Order::List`.
+pub fn sort(
+    dir_contents: &mut DirContents,
+    order: Order,
+    ss: &SyntaxSet,
+    list: Option<&Path>,
+) -> anyhow::Result<()> {
+    let mut files = std::mem::take(&mut dir_contents.children_content);
+    let result = sort_files(&mut files, dir_contents, order, ss, list);
+    files.sort_by_key(|file| file.root);
+    dir_contents.children_content = files;
+    result
+}
+
+fn sort_files(
+    files: &mut [content::File],
+    dir_contents: &DirContents,
+    order: Order,
+    ss: &SyntaxSet,
+    list: Option<&Path>,
+) -> anyhow::Result<()> {
+    if order == Order::Given {
+        return Ok(());
+    }
+    files.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+    match order {
+        Order::Path | Order::Given => {}
+        Order::Size => files.sort_by_key(|file| std::cmp::Reverse(file.len)),
+        Order::Lines => files.sort_by_key(|file| std::cmp::Reverse(file.lines)),
+        Order::Language => files.sort_by_cached_key(|file| {
+            let syntax = file.syntax(ss).ok().flatten();
+            (syntax.is_none(), syntax.map(|syntax| syntax.name.clone()))
+        }),
+        Order::Modified => files.sort_by_cached_key(|content::File { path, .. }| {
+            std::cmp::Reverse(
+                std::fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH),
+            )
+        }),
+        Order::Churn => {
+            let churn = dir_contents
+                .roots
+                .iter()
+                .map(|root| churn(&root.dir))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            files.sort_by_cached_key(|file| {
+                std::cmp::Reverse(
+                    churn[file.root]
+                        .get(dir_contents.relative_path(file))
+                        .copied()
+                        .unwrap_or(0),
+                )
+            })
+        }
+        Order::List => {
+            let list = list.context("Sorting by list requires a file with paths")?;
+            let mut positions = HashMap::new();
+            let list = std::fs::read_to_string(list)
+                .with_context(|| format!("Failed to read sort list at {list:?}"))?;
+            for path in list
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            {
+                let position = positions.len();
+                positions
+                    .entry(normalize(Path::new(path)))
+                    .or_insert(position);
+            }
+            files.sort_by_cached_key(|file| {
+                positions
+                    .get(dir_contents.label(file).as_ref())
+                    .copied()
+                    .unwrap_or(usize::MAX)
+            })
+        }
+    }
+    Ok(())
+}
+
+/// Count the commits touching each file in `dir` by its path relative to `dir`, where `dir` may also
+/// be an archive without history.
+fn churn(dir: &Path) -> anyhow::Result<HashMap<PathBuf, usize>> {
+    if !dir.is_dir() {
+        return Ok(HashMap::new());
+    }
+    let output = Command::new("git")
+        .args([
+            "-c",
+            "core.quotePath=false",
+            "log",
+            "--format=",
+            "--name-only",
+            "--relative",
+        ])
+        .current_dir(dir)
+        .output()
+        .context("Failed to run git, is it installed?")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to read the git history of {dir:?}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+    }
+    let mut churn = HashMap::new();
+    for path in String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.is_empty())
+    {
+        *churn.entry(PathBuf::from(path)).or_default() += 1;
+    }
+    Ok(churn)
+}
+
+/// Remove `.` components so paths written differently compare equal.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| component != &std::path::Component::CurDir)
+        .collect()
+}
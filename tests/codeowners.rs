@@ -0,0 +1,68 @@
+use codevis::codeowners::CodeOwners;
+use std::path::Path;
+
+fn owners<'a>(code_owners: &'a CodeOwners, path: &str) -> &'a [String] {
+    code_owners.owners_of(Path::new(path))
+}
+
+#[test]
+fn last_matching_rule_wins() {
+    let code_owners = CodeOwners::from_path(
+        Path::new("tests/fixtures/CODEOWNERS"),
+        Path::new("."),
+        Default::default(),
+    )
+    .unwrap();
+
+    assert_eq!(owners(&code_owners, "src/lib.rs"), ["@codevis/core"]);
+    assert_eq!(owners(&code_owners, "README.md"), ["@docs-team"]);
+    assert_eq!(
+        owners(&code_owners, "src/render/chunk.rs"),
+        ["@codevis/render", "@alice"]
+    );
+    assert_eq!(
+        owners(&code_owners, "src/overlay/coverage.rs"),
+        ["@codevis/overlays"]
+    );
+    assert!(
+        owners(&code_owners, "src/options.rs").is_empty(),
+        "rules without owners make files unowned"
+    );
+    assert_eq!(
+        owners(&code_owners, "tests/fixtures/profile.folded"),
+        ["@codevis/qa"],
+        "GitLab sections provide default owners"
+    );
+    assert_eq!(owners(&code_owners, "tests/file with spaces.txt"), ["@bob"]);
+}
+
+#[test]
+fn paths_are_relative_to_the_rendered_directory() {
+    let code_owners = CodeOwners::from_path(
+        Path::new("tests/fixtures/CODEOWNERS"),
+        Path::new("."),
+        "src".into(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        owners(&code_owners, "render/function.rs"),
+        ["@codevis/render", "@alice"]
+    );
+    assert_eq!(owners(&code_owners, "lib.rs"), ["@codevis/core"]);
+}
+
+#[test]
+fn files_outside_of_the_repository_are_unowned() {
+    let root = Path::new(".").canonicalize().unwrap();
+    let code_owners =
+        CodeOwners::from_path(Path::new("tests/fixtures/CODEOWNERS"), &root, "src".into()).unwrap();
+
+    assert_eq!(
+        code_owners.owners_of(&root.join("src/lib.rs")),
+        ["@codevis/core"],
+        "absolute paths within the repository are matched"
+    );
+    let outside = root.parent().unwrap().join("outside/lib.rs");
+    assert!(code_owners.owners_of(&outside).is_empty());
+}
@@ -0,0 +1,12 @@
+use std::io::Read;
+
+fn main() {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input).unwrap();
+    for line in input.lines() {
+        if line.is_empty() {
+            continue;
+        }
+        println!("{}", ui::view::render(line));
+    }
+}
@@ -0,0 +1,6 @@
+pub fn render(line: &str) -> String {
+    match line.split_once('=') {
+        Some((key, value)) => format!("{key:>12} | {value}"),
+        None => line.to_owned(),
+    }
+}
@@ -0,0 +1,5 @@
+//! Parse `key=value` pairs.
+
+pub mod parse;
+
+pub use parse::pairs;
@@ -0,0 +1,20 @@
+use std::collections::BTreeMap;
+
+/// Collect all `key=value` pairs of `input`, ignoring other lines.
+pub fn pairs(input: &str) -> BTreeMap<&str, &str> {
+    input
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn pairs() {
+        let pairs = super::pairs("a = 1\nb=2\nignored");
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs.get("a").copied().unwrap(), "1");
+    }
+}
@@ -0,0 +1,878 @@
+use codevis::Filter;
+use std::path::Path;
+
+fn read(filter: Filter) -> (Vec<String>, usize) {
+    let (contents, skipped) = codevis::unicode_content(
+        Path::new("./src/"),
+        &filter,
+        prodash::progress::Discard,
+        &Default::default(),
+    )
+    .unwrap();
+    let mut paths: Vec<_> = contents
+        .children_content
+        .iter()
+        .map(|file| contents.relative_path(file).to_string_lossy().into_owned())
+        .collect();
+    paths.sort();
+    (paths, skipped.extension)
+}
+
+#[test]
+fn include_and_exclude_globs_work_together() {
+    let (paths, _) = read(Filter {
+        include: vec!["render/**".into(), "lib.rs".into()],
+        exclude: vec!["*.rs".into(), "!render/mod.rs".into(), "!lib.rs".into()],
+        ..Default::default()
+    });
+    assert_eq!(paths, ["lib.rs", "render/mod.rs"]);
+
+    let (paths, _) = read(Filter {
+        include: vec!["render/".into(), "!render/chunk.rs".into()],
+        exclude: vec!["overlay/".into()],
+        ..Default::default()
+    });
+    assert!(paths.iter().all(|path| path.starts_with("render/")));
+    assert!(paths.contains(&"render/function.rs".to_owned()));
+    assert!(!paths.contains(&"render/chunk.rs".to_owned()));
+}
+
+#[test]
+fn globs_match_below_a_root_whose_name_prefixes_a_subdirectory() {
+    // the root has to be relative to the current directory, which is only changed in a child process
+    // so other tests aren't affected.
+    let Some(dir) = std::env::var_os("CODEVIS_TEST_CURRENT_DIR") else {
+        let dir = std::env::temp_dir().join(format!("codevis-glob-root-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("app/application")).unwrap();
+        std::fs::write(dir.join("app/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(dir.join("app/application/x.rs"), "fn x() {}\n").unwrap();
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "globs_match_below_a_root_whose_name_prefixes_a_subdirectory",
+            ])
+            .env("CODEVIS_TEST_CURRENT_DIR", &dir)
+            .status()
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(status.success());
+        return;
+    };
+    std::env::set_current_dir(dir).unwrap();
+    let read = |filter: Filter| {
+        let (contents, _) = codevis::unicode_content(
+            Path::new("app"),
+            &filter,
+            prodash::progress::Discard,
+            &Default::default(),
+        )
+        .unwrap();
+        let mut paths: Vec<_> = contents
+            .children_content
+            .iter()
+            .map(|file| contents.relative_path(file).to_string_lossy().into_owned())
+            .collect();
+        paths.sort();
+        paths
+    };
+    assert_eq!(
+        read(Filter {
+            exclude: vec!["application/**".into()],
+            ..Default::default()
+        }),
+        ["main.rs"]
+    );
+    assert_eq!(
+        read(Filter {
+            include: vec!["application/**".into()],
+            ..Default::default()
+        }),
+        ["application/x.rs"]
+    );
+
+    let (contents, _) = codevis::unicode_content_of_files(
+        Path::new("app"),
+        &["main.rs".into(), "application/x.rs".into()],
+        &Filter {
+            exclude: vec!["application/**".into()],
+            ..Default::default()
+        },
+        prodash::progress::Discard,
+        &Default::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        contents.children_content.len(),
+        1,
+        "listed files are matched the same way"
+    );
+}
+
+#[test]
+fn extension_filters_apply_together_with_globs() {
+    let (all, ignored) = read(Filter::default());
+    assert_eq!(ignored, 0);
+
+    let (paths, ignored) = read(Filter {
+        whitelist_extensions: vec!["rs".into()],
+        ignore_extensions: vec!["rs".into()],
+        ..Default::default()
+    });
+    assert!(paths.is_empty(), "ignoring wins over the whitelist");
+    assert_eq!(ignored, all.len());
+
+    let (paths, _) = read(Filter {
+        whitelist_extensions: vec!["rs".into()],
+        include: vec!["overlay/**".into()],
+        ..Default::default()
+    });
+    assert!(!paths.is_empty());
+    assert!(paths.iter().all(|path| path.starts_with("overlay/")));
+}
+
+#[test]
+fn generated_files_are_recognized_by_path_and_content() {
+    use codevis::generated::{classify, Kind};
+
+    let path = Path::new;
+    assert_eq!(classify(path("src/lib.rs"), "fn main() {}"), None);
+    assert_eq!(
+        classify(path("web/node_modules/react/index.js"), ""),
+        Some(Kind::Vendored)
+    );
+    assert_eq!(
+        classify(path("vendor.rs"), ""),
+        None,
+        "only directories are vendored"
+    );
+    assert_eq!(classify(path("Cargo.lock"), ""), Some(Kind::Lockfile));
+    assert_eq!(
+        classify(
+            path("src/parser.rs"),
+            "// @generated by lalrpop\nfn parse() {}"
+        ),
+        Some(Kind::Generated)
+    );
+    assert_eq!(
+        classify(
+            path("api.go"),
+            "// Code generated by protoc-gen-go. DO NOT EDIT.\n"
+        ),
+        Some(Kind::Generated)
+    );
+    assert_eq!(
+        classify(
+            path("src/lib.rs"),
+            &format!("{}// DO NOT EDIT\n", "\n".repeat(10)),
+        ),
+        None,
+        "markers are only searched in the first lines"
+    );
+    assert_eq!(classify(path("app.min.js"), ""), Some(Kind::Minified));
+    assert_eq!(
+        classify(path("bundle.js"), &"x".repeat(4096)),
+        Some(Kind::Minified)
+    );
+    assert_eq!(classify(path("short.js"), &"x".repeat(300)), None);
+}
+
+#[test]
+fn generated_files_can_be_skipped() {
+    let (all, _) = read(Filter::default());
+    let (paths, _) = read(Filter {
+        generated: codevis::generated::Treatment::Skip,
+        ..Default::default()
+    });
+    assert_eq!(paths, all, "none of our own sources is generated");
+}
+
+#[test]
+fn invalid_gitattributes_lines_are_skipped_with_a_warning() {
+    let dir = std::env::temp_dir().join(format!("codevis-bad-attributes-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(
+        dir.join("sub/.gitattributes"),
+        "[unclosed binary\n*.bin binary\n",
+    )
+    .unwrap();
+
+    let attributes = codevis::attributes::Attributes::new(&dir).unwrap();
+    assert!(attributes.of(Path::new("sub/data.bin")).binary);
+    assert!(!attributes.of(Path::new("sub/main.rs")).binary);
+    let warnings = attributes.take_warnings();
+    assert_eq!(warnings.len(), 1, "the file is only read once");
+    assert!(warnings[0].contains("[unclosed"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn gitattributes_and_codevisignore_declare_what_to_render() {
+    use codevis::generated::Treatment;
+
+    let dir = std::env::temp_dir().join(format!("codevis-attributes-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (path, content) in [
+        (
+            ".gitattributes",
+            "*.pb.rs linguist-generated\nassets/** binary\n",
+        ),
+        (".codevisignore", "scratch/\n"),
+        ("main.rs", "fn main() {}\n"),
+        ("api.pb.rs", "struct Api;\n"),
+        ("assets/logo.txt", "not really text\n"),
+        ("scratch/notes.md", "# Notes\n"),
+        ("third_party/lib.rs", "pub fn vendored() {}\n"),
+        (
+            "third_party/.gitattributes",
+            "*.rs -linguist-vendored\nlib.rs !binary\n",
+        ),
+        ("Cargo.lock", "# lockfile\n"),
+    ] {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    let read = |generated| {
+        let (contents, skipped) = codevis::unicode_content(
+            &dir,
+            &Filter {
+                generated,
+                ..Default::default()
+            },
+            prodash::progress::Discard,
+            &Default::default(),
+        )
+        .unwrap();
+        let relative = |path: &Path| path.strip_prefix(&dir).unwrap().to_owned();
+        let mut paths: Vec<_> = contents
+            .children_content
+            .iter()
+            .map(|file| relative(&file.path))
+            .collect();
+        paths.sort();
+        let mut generated: Vec<_> = contents.generated.iter().map(|p| relative(p)).collect();
+        generated.sort();
+        (paths, generated, skipped)
+    };
+
+    let (paths, generated, skipped) = read(Treatment::Skip);
+    assert_eq!(
+        paths,
+        [Path::new("main.rs"), Path::new("third_party/lib.rs")],
+        "attributes in deeper directories take precedence over heuristics"
+    );
+    assert!(generated.is_empty());
+    assert_eq!((skipped.generated, skipped.binary), (2, 1));
+
+    let (paths, generated, skipped) = read(Treatment::Dim);
+    assert_eq!(paths.len(), 4);
+    assert_eq!(
+        generated,
+        [Path::new("Cargo.lock"), Path::new("api.pb.rs")],
+        "generated files are kept, but recorded to be dimmed"
+    );
+    assert_eq!((skipped.generated, skipped.binary), (0, 1));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn files_are_decoded_from_their_detected_encoding() {
+    use codevis::encoding::decode;
+
+    let decoded = decode(b"fn main() {}\n".to_vec()).unwrap();
+    assert_eq!(
+        (decoded.content.as_str(), decoded.encoding.name()),
+        ("fn main() {}\n", "UTF-8")
+    );
+
+    let decoded = decode(b"\xef\xbb\xbfbom".to_vec()).unwrap();
+    assert_eq!(decoded.content, "bom", "byte order marks are removed");
+
+    let utf16: Vec<u8> = "/* \u{e9}t\u{e9} */\n"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    for bytes in [[b"\xff\xfe".as_slice(), &utf16].concat(), utf16] {
+        let decoded = decode(bytes).unwrap();
+        assert_eq!(
+            (decoded.content.as_str(), decoded.encoding.name()),
+            ("/* \u{e9}t\u{e9} */\n", "UTF-16LE")
+        );
+    }
+
+    let decoded = decode(b"/* Gr\xfc\xdfe aus K\xf6ln, \xe0 bient\xf4t */\n".to_vec()).unwrap();
+    assert_eq!(
+        (decoded.content.as_str(), decoded.encoding.name()),
+        (
+            "/* Gr\u{fc}\u{df}e aus K\u{f6}ln, \u{e0} bient\u{f4}t */\n",
+            "windows-1252"
+        )
+    );
+
+    let (shift_jis, _, _) =
+        encoding_rs::SHIFT_JIS.encode("// こんにちは、世界。日本語のコメントです。\n");
+    let decoded = decode(shift_jis.into_owned()).unwrap();
+    assert_eq!(
+        (decoded.content.as_str(), decoded.encoding.name()),
+        ("// こんにちは、世界。日本語のコメントです。\n", "Shift_JIS")
+    );
+    assert!(!decoded.had_errors);
+
+    assert!(
+        decode(b"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00\xff\xfe\xfd".to_vec()).is_err(),
+        "binary files aren't decoded"
+    );
+}
+
+#[test]
+fn binary_files_are_skipped_or_read_as_hex_dumps() {
+    use codevis::encoding::{hex_dump, is_binary};
+
+    assert!(!is_binary(
+        b"fn main() {\n\tprintln!(\"\x1b[1mbold\x1b[0m\");\r\n}\x0c"
+    ));
+    assert!(is_binary(b"valid UTF-8 with a \0 byte"));
+    assert!(
+        is_binary(b"\x01\x02\x03\x04 mostly control characters \x05\x06\x07\x08"),
+        "valid UTF-8 can be binary"
+    );
+    assert_eq!(hex_dump(&[0x00, 0x7f, 0xff]), "007fff\n");
+    assert_eq!(hex_dump(&[0xab; 33]).lines().count(), 2);
+
+    let dir = std::env::temp_dir().join(format!("codevis-binary-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("blob.bin"), b"\0\x01\x02\xff").unwrap();
+    std::fs::write(dir.join("text.txt"), "text\n").unwrap();
+
+    let read = |hex_binaries| {
+        codevis::unicode_content(
+            &dir,
+            &Filter {
+                hex_binaries,
+                ..Default::default()
+            },
+            prodash::progress::Discard,
+            &Default::default(),
+        )
+        .unwrap()
+    };
+    let (contents, skipped) = read(false);
+    assert_eq!(contents.children_content.len(), 1);
+    assert_eq!(skipped.binary, 1);
+
+    let (contents, skipped) = read(true);
+    assert_eq!(skipped.binary, 0);
+    let blob = dir.join("blob.bin");
+    let file = contents
+        .children_content
+        .iter()
+        .find(|file| file.path == blob)
+        .unwrap();
+    assert_eq!(file.content().unwrap(), "000102ff\n");
+    assert_eq!(contents.binary.into_iter().collect::<Vec<_>>(), [blob]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn oversized_files_are_skipped_or_truncated() {
+    let dir = std::env::temp_dir().join(format!("codevis-limits-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("small.rs"), "fn a() {}\n").unwrap();
+    std::fs::write(dir.join("long.rs"), "// line\n".repeat(100)).unwrap();
+    std::fs::write(dir.join("wide.rs"), "// wide line\n".repeat(30)).unwrap();
+
+    let read = |max_file_size, max_file_lines, truncate| {
+        let (contents, skipped) = codevis::unicode_content(
+            &dir,
+            &Filter {
+                max_file_size,
+                max_file_lines,
+                truncate,
+                ..Default::default()
+            },
+            prodash::progress::Discard,
+            &Default::default(),
+        )
+        .unwrap();
+        let mut files: Vec<_> = contents
+            .children_content
+            .into_iter()
+            .map(|file| {
+                let name = file
+                    .path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned();
+                (name, file.lines, file.content().unwrap().into_owned())
+            })
+            .collect();
+        files.sort();
+        (files, contents.truncated.len(), skipped.too_large)
+    };
+
+    let (files, truncated, too_large) = read(Some(200), Some(20), false);
+    assert_eq!(files.len(), 1);
+    assert_eq!((truncated, too_large), (0, 2));
+
+    let (files, truncated, too_large) = read(Some(200), Some(20), true);
+    assert_eq!((truncated, too_large), (2, 0));
+    let lines: Vec<_> = files
+        .iter()
+        .map(|(name, lines, _)| (name.as_str(), *lines))
+        .collect();
+    assert_eq!(
+        lines,
+        [("long.rs", 21), ("small.rs", 1), ("wide.rs", 16)],
+        "limits apply in lines, and files cut by size lose their partial last line"
+    );
+    for (name, _, content) in &files {
+        assert_eq!(
+            content.lines().last() == Some(codevis::TRUNCATION_MARKER),
+            name != "small.rs",
+            "{name}"
+        );
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn truncation_by_size_does_not_split_characters() {
+    for max_file_size in [8, 13] {
+        let (contents, _) = codevis::unicode_content_of_reader(
+            "notes.txt",
+            "café\nrésumé\n".as_bytes(),
+            &Filter {
+                max_file_size: Some(max_file_size),
+                truncate: true,
+                ..Default::default()
+            },
+            prodash::progress::Discard,
+        )
+        .unwrap();
+        assert_eq!(
+            contents.children_content[0].content().unwrap(),
+            format!("café\n{}\n", codevis::TRUNCATION_MARKER),
+            "the file is still UTF-8 when cut within the `é` of line 2 after {max_file_size} bytes"
+        );
+    }
+}
+
+#[test]
+fn endless_input_is_only_read_up_to_the_maximum_size() {
+    let read = |truncate| {
+        codevis::unicode_content_of_reader(
+            "stdin",
+            std::io::repeat(b'\n'),
+            &Filter {
+                max_file_size: Some(16),
+                truncate,
+                ..Default::default()
+            },
+            prodash::progress::Discard,
+        )
+        .unwrap()
+    };
+    let (contents, skipped) = read(false);
+    assert!(contents.children_content.is_empty());
+    assert_eq!(skipped.too_large, 1);
+
+    let (contents, _) = read(true);
+    assert_eq!(contents.children_content[0].lines, 16 + 1);
+    assert_eq!(contents.truncated.len(), 1);
+}
+
+#[test]
+fn file_contents_are_loaded_when_needed_with_their_discovered_line_count() {
+    let dir = std::env::temp_dir().join(format!("codevis-lazy-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("lib.rs");
+    let write_utf16 = |content: &str| {
+        let bytes = [0xff, 0xfe]
+            .into_iter()
+            .chain(content.encode_utf16().flat_map(u16::to_le_bytes));
+        std::fs::write(&path, bytes.collect::<Vec<_>>()).unwrap();
+    };
+    write_utf16("// \u{e9}t\u{e9}\nfn main() {}\n");
+
+    let (contents, _) = codevis::unicode_content(
+        &dir,
+        &Filter::default(),
+        prodash::progress::Discard,
+        &Default::default(),
+    )
+    .unwrap();
+    let file = &contents.children_content[0];
+    assert_eq!((file.lines, file.len), (2, 22));
+    assert_eq!(file.content().unwrap(), "// \u{e9}t\u{e9}\nfn main() {}\n");
+
+    write_utf16("fn a() {}\nfn b() {}\nfn c() {}\n");
+    assert_eq!(
+        file.content().unwrap(),
+        "fn a() {}\nfn b() {}\n",
+        "the layout depends on the line count, so changed files are cut to it"
+    );
+    std::fs::write(&path, "").unwrap();
+    assert_eq!(file.content().unwrap(), "\n\n", "or padded to it");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn file_lists_are_split_by_newlines_or_nul_bytes() {
+    use codevis::parse_file_list;
+    use std::path::PathBuf;
+
+    let expected = [PathBuf::from("src/lib.rs"), PathBuf::from("a file.rs")];
+    assert_eq!(
+        parse_file_list(b"src/lib.rs\r\na file.rs\n\n").unwrap(),
+        expected
+    );
+    assert_eq!(
+        parse_file_list(b"src/lib.rs\0a file.rs\0").unwrap(),
+        expected
+    );
+    assert_eq!(
+        parse_file_list(b"with\nnewline\0").unwrap(),
+        [PathBuf::from("with\nnewline")],
+        "NUL separated paths may contain newlines"
+    );
+}
+
+#[test]
+fn listed_files_are_read_in_order_without_walking() {
+    let paths = ["render/mod.rs", "lib.rs", "missing.rs", "overlay/mod.rs"].map(Into::into);
+    let (contents, skipped) = codevis::unicode_content_of_files(
+        Path::new("src"),
+        &paths,
+        &Filter {
+            exclude: vec!["overlay/".into()],
+            ..Default::default()
+        },
+        prodash::progress::Discard,
+        &Default::default(),
+    )
+    .unwrap();
+    let paths: Vec<_> = contents
+        .children_content
+        .iter()
+        .map(|file| contents.relative_path(file))
+        .collect();
+    assert_eq!(paths, [Path::new("render/mod.rs"), Path::new("lib.rs")]);
+    assert_eq!(skipped.unreadable, 1);
+}
+
+#[test]
+fn archives_are_read_without_extracting_them() {
+    use std::io::Write;
+
+    let dir = std::env::temp_dir().join(format!("codevis-archives-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let entries: [(&str, &[u8]); 4] = [
+        ("drop/src/main.rs", b"fn main() {}\n"),
+        ("drop/README.md", b"# Drop\n"),
+        ("drop/logo.png", b"\x89PNG\x00\x00\x00"),
+        ("./drop/Cargo.lock", b"version = 3\n"),
+    ];
+
+    let tar_gz = dir.join("drop-1.0.tar.gz");
+    let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(
+        std::fs::File::create(&tar_gz).unwrap(),
+        Default::default(),
+    ));
+    for (path, content) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, path, content).unwrap();
+    }
+    tar.into_inner().unwrap().finish().unwrap();
+
+    let zip_path = dir.join("drop-1.0.ZIP");
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&zip_path).unwrap());
+    zip.add_directory("drop/src", Default::default()).unwrap();
+    for (path, content) in entries {
+        zip.start_file(path, Default::default()).unwrap();
+        zip.write_all(content).unwrap();
+    }
+    zip.finish().unwrap();
+
+    for archive in [&tar_gz, &zip_path] {
+        let (contents, skipped) = codevis::archive::unicode_content(
+            archive,
+            &Filter {
+                exclude: vec!["*.md".into()],
+                generated: codevis::generated::Treatment::Skip,
+                ..Default::default()
+            },
+            prodash::progress::Discard,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(contents.roots[0].name, "drop-1.0");
+        let files: Vec<_> = contents
+            .children_content
+            .iter()
+            .map(|file| (contents.relative_path(file), file.content().unwrap()))
+            .collect();
+        assert_eq!(
+            files,
+            [(Path::new("drop/src/main.rs"), "fn main() {}\n".into())],
+            "{archive:?}"
+        );
+        assert_eq!((skipped.binary, skipped.generated), (1, 1));
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn single_files_and_text_from_readers_can_be_rendered() {
+    let (contents, _) = codevis::unicode_content(
+        Path::new("src/lib.rs"),
+        &Filter::default(),
+        prodash::progress::Discard,
+        &Default::default(),
+    )
+    .unwrap();
+    assert_eq!(contents.children_content.len(), 1);
+    assert_eq!(
+        contents.relative_path(&contents.children_content[0]),
+        Path::new("lib.rs")
+    );
+
+    let (mut contents, _) = codevis::unicode_content_of_reader(
+        "stdin",
+        &b"fn main() {}\n"[..],
+        &Filter::default(),
+        prodash::progress::Discard,
+    )
+    .unwrap();
+    let file = &mut contents.children_content[0];
+    assert_eq!(file.content().unwrap(), "fn main() {}\n");
+
+    let ss = syntect::parsing::SyntaxSet::load_defaults_newlines();
+    assert!(file.syntax(&ss).unwrap().is_none());
+    file.language = Some("rs".into());
+    assert_eq!(file.syntax(&ss).unwrap().unwrap().name, "Rust");
+}
+
+#[test]
+fn syntax_is_detected_by_first_line_without_reading_from_disk() {
+    let ss = syntect::parsing::SyntaxSet::load_defaults_newlines();
+    let file = codevis::content::File::in_memory(
+        "drop.tar/bin/run".into(),
+        "#!/usr/bin/env python\nprint()\n".into(),
+    );
+    assert_eq!(file.syntax(&ss).unwrap().unwrap().name, "Python");
+}
+
+#[test]
+fn syntax_is_detected_by_first_line_recorded_when_reading_files() {
+    let dir = std::env::temp_dir().join(format!("codevis-first-line-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("run"), "#!/usr/bin/env python\nprint()\n").unwrap();
+    let (contents, _) = codevis::unicode_content(
+        &dir,
+        &Filter::default(),
+        prodash::progress::Discard,
+        &Default::default(),
+    )
+    .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let ss = syntect::parsing::SyntaxSet::load_defaults_newlines();
+    assert_eq!(
+        contents.children_content[0]
+            .syntax(&ss)
+            .unwrap()
+            .unwrap()
+            .name,
+        "Python",
+        "the file isn't read again to find its syntax"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn walking_can_include_hidden_files_follow_symlinks_and_skip_ignore_files() {
+    let base = std::env::temp_dir().join(format!("codevis-walk-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&base);
+    let dir = base.join("repo");
+    let shared = base.join("shared");
+    for path in [".git", "sub/deep", "../shared"] {
+        std::fs::create_dir_all(dir.join(path)).unwrap();
+    }
+    for (path, content) in [
+        (".git/config", "[core]\n"),
+        (".gitignore", "ignored.rs\n"),
+        (".codevisignore", "custom.rs\n"),
+        (".hidden.rs", "fn hidden() {}\n"),
+        ("main.rs", "fn main() {}\n"),
+        ("ignored.rs", "fn ignored() {}\n"),
+        ("custom.rs", "fn custom() {}\n"),
+        ("sub/deep/deep.rs", "fn deep() {}\n"),
+    ] {
+        std::fs::write(dir.join(path), content).unwrap();
+    }
+    std::fs::write(shared.join("shared.rs"), "fn shared() {}\n").unwrap();
+    std::os::unix::fs::symlink(&shared, dir.join("shared")).unwrap();
+    std::os::unix::fs::symlink(shared.join("shared.rs"), dir.join("link.rs")).unwrap();
+    std::os::unix::fs::symlink(&dir, dir.join("sub/loop")).unwrap();
+
+    let read = |filter: Filter| {
+        let (contents, skipped) = codevis::unicode_content(
+            &dir,
+            &filter,
+            prodash::progress::Discard,
+            &Default::default(),
+        )
+        .unwrap();
+        let mut paths: Vec<_> = contents
+            .children_content
+            .iter()
+            .map(|file| contents.relative_path(file).to_string_lossy().into_owned())
+            .collect();
+        paths.sort();
+        assert_eq!(
+            skipped.unreadable, 0,
+            "links are neither read nor tried to be read unless followed"
+        );
+        (paths, skipped.symlink_loops)
+    };
+
+    assert_eq!(
+        read(Filter::default()),
+        (vec!["main.rs".into(), "sub/deep/deep.rs".into()], 0)
+    );
+    assert_eq!(
+        read(Filter {
+            hidden: true,
+            max_depth: Some(1),
+            ..Default::default()
+        }),
+        (
+            vec![
+                ".codevisignore".into(),
+                ".gitignore".into(),
+                ".hidden.rs".into(),
+                "main.rs".into()
+            ],
+            0
+        ),
+        ".git is never read"
+    );
+    assert_eq!(
+        read(Filter {
+            no_ignore_vcs: true,
+            max_depth: Some(1),
+            ..Default::default()
+        }),
+        (vec!["ignored.rs".into(), "main.rs".into()], 0)
+    );
+    assert_eq!(
+        read(Filter {
+            no_ignore: true,
+            max_depth: Some(1),
+            ..Default::default()
+        }),
+        (
+            vec!["custom.rs".into(), "ignored.rs".into(), "main.rs".into()],
+            0
+        )
+    );
+    assert_eq!(
+        read(Filter {
+            follow_symlinks: true,
+            ..Default::default()
+        }),
+        (
+            vec![
+                "link.rs".into(),
+                "main.rs".into(),
+                "shared/shared.rs".into(),
+                "sub/deep/deep.rs".into()
+            ],
+            1
+        ),
+        "loops are counted and skipped"
+    );
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn files_are_read_in_the_same_order_by_any_number_of_threads() {
+    let read = |threads: usize| {
+        let (contents, skipped) = codevis::unicode_content(
+            Path::new("./src/"),
+            &Filter {
+                ignore_extensions: vec!["toml".into()],
+                threads,
+                ..Default::default()
+            },
+            prodash::progress::Discard,
+            &Default::default(),
+        )
+        .unwrap();
+        let paths: Vec<_> = contents
+            .children_content
+            .iter()
+            .map(|file| (file.path.clone(), file.lines))
+            .collect();
+        (paths, skipped.extension)
+    };
+
+    let (single, _) = read(1);
+    let mut sorted = single.clone();
+    sorted.sort();
+    assert_eq!(single, sorted, "files are read in the order of their path");
+    for threads in [2, 8, 0] {
+        assert_eq!(read(threads), read(1));
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn only_regular_files_are_read_as_others_could_block() {
+    let dir = std::env::temp_dir().join(format!("codevis-fifo-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+    let status = std::process::Command::new("mkfifo")
+        .arg(dir.join("pipe.rs"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let (contents, skipped) = codevis::unicode_content(
+        &dir,
+        &Filter::default(),
+        prodash::progress::Discard,
+        &Default::default(),
+    )
+    .unwrap();
+    assert_eq!(contents.children_content.len(), 1);
+    assert_eq!(skipped.unreadable, 0, "the FIFO isn't even considered");
+
+    let (contents, skipped) = codevis::unicode_content_of_files(
+        &dir,
+        &["pipe.rs".into(), "main.rs".into()],
+        &Filter::default(),
+        prodash::progress::Discard,
+        &Default::default(),
+    )
+    .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(contents.children_content.len(), 1);
+    assert_eq!(skipped.unreadable, 1, "listed FIFOs are skipped");
+}
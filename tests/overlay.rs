@@ -0,0 +1,323 @@
+use codevis::overlay::{annotations, blame, coverage, diagnostics, profile, RootPaths};
+use codevis::render;
+use std::path::Path;
+
+#[test]
+fn coverage_reports_in_all_formats_yield_the_same_overlay() {
+    for report in ["coverage.info", "coverage.xml", "coverage.json"] {
+        let overlay = coverage::load(&Path::new("tests/fixtures").join(report)).unwrap();
+        let root = RootPaths::new(Path::new("/home/user/codevis"));
+        let path = Path::new("./src/lib.rs");
+        assert_eq!(
+            [1, 2, 3, 4].map(|line| overlay.color(&root, path, line)),
+            [
+                Some(coverage::COVERED),
+                Some(coverage::UNCOVERED),
+                Some(coverage::PARTIAL),
+                None
+            ],
+            "{report}"
+        );
+        assert_eq!(
+            overlay.color(&root, Path::new("lib.rs"), 1),
+            None,
+            "paths aren't matched by their suffix"
+        );
+        assert_eq!(overlay.color(&root, Path::new("main.rs"), 1), None);
+    }
+}
+
+#[test]
+fn overlay_paths_are_resolved_against_the_rendered_directory_and_its_repository() {
+    let repo = std::env::temp_dir().join(format!("codevis-overlay-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&repo);
+    std::fs::create_dir_all(repo.join(".git")).unwrap();
+    std::fs::create_dir_all(repo.join("src")).unwrap();
+    let src = repo.join("src").canonicalize().unwrap();
+    let report = repo.join("coverage.info");
+    std::fs::write(
+        &report,
+        format!(
+            "SF:{}\nDA:1,1\nend_of_record\n",
+            src.join("main.rs").display()
+        ),
+    )
+    .unwrap();
+    let absolute = coverage::load(&report).unwrap();
+    let relative_to_repo = coverage::load(Path::new("tests/fixtures/coverage.xml")).unwrap();
+    let root = RootPaths::new(&src);
+    std::fs::remove_dir_all(&repo).unwrap();
+
+    assert_eq!(
+        absolute.color(&root, Path::new("main.rs"), 1),
+        Some(coverage::COVERED)
+    );
+    assert_eq!(
+        relative_to_repo.color(&root, Path::new("lib.rs"), 1),
+        Some(coverage::COVERED),
+        "`src/lib.rs` is relative to the repository containing the rendered `src` directory"
+    );
+    assert_eq!(
+        relative_to_repo.color(
+            &RootPaths::new(Path::new("/elsewhere")),
+            Path::new("lib.rs"),
+            1
+        ),
+        None
+    );
+}
+
+#[test]
+fn diagnostics_color_lines_by_their_most_severe_diagnostic() {
+    for report in ["diagnostics.sarif", "diagnostics.json"] {
+        let overlay = diagnostics::load(&Path::new("tests/fixtures").join(report)).unwrap();
+        let root = RootPaths::new(Path::new("/home/user/codevis"));
+        let path = Path::new("src/lib.rs");
+        assert_eq!(
+            [1, 2, 3].map(|line| overlay.color(&root, path, line)),
+            [
+                None,
+                Some(diagnostics::Severity::Warning.color()),
+                Some(diagnostics::Severity::Error.color())
+            ],
+            "{report}"
+        );
+        assert_eq!(
+            overlay.color(&root, Path::new("src/main.rs"), 9),
+            None,
+            "only primary spans are used"
+        );
+        assert_eq!(
+            overlay
+                .legend
+                .iter()
+                .map(|(_, label)| label.as_str())
+                .collect::<Vec<_>>(),
+            ["1 error", "1 warning", "0 notes"]
+        );
+    }
+}
+
+#[test]
+fn profile_colors_lines_by_inclusive_sample_count() {
+    let overlay = profile::load(
+        Path::new("tests/fixtures/profile.folded"),
+        codevis::overlay::Scale::Linear,
+    )
+    .unwrap();
+    let root = RootPaths::new(Path::new("/home/user/codevis"));
+    let hottest = overlay.color(&root, Path::new("src/main.rs"), 10);
+    assert_eq!(
+        hottest,
+        Some(*render::color::FLAME_GRADIENT.last().unwrap()),
+        "all samples passed through main"
+    );
+    let render_line = overlay.color(&root, Path::new("src/render/function.rs"), 42);
+    assert!(render_line.is_some());
+    assert_ne!(
+        render_line, hottest,
+        "99 of 100 samples, counted once per stack"
+    );
+    assert_eq!(overlay.color(&root, Path::new("src/main.rs"), 11), None);
+    assert_eq!(overlay.legend.last().unwrap().1, "100 samples");
+}
+
+#[test]
+fn annotations_map_values_onto_the_gradient_and_use_colors_as_is() {
+    for input in ["annotations.csv", "annotations.jsonl"] {
+        let overlay = annotations::load(
+            &Path::new("tests/fixtures").join(input),
+            &render::color::HEAT_GRADIENT,
+            codevis::overlay::Scale::Linear,
+        )
+        .unwrap();
+        let root = RootPaths::new(Path::new("/home/user/codevis"));
+        let lib = Path::new("src/lib.rs");
+        assert_eq!(
+            [1, 2, 3, 4].map(|line| overlay.color(&root, lib, line)),
+            [
+                Some(render::color::HEAT_GRADIENT[0]),
+                Some(render::color::HEAT_GRADIENT[0]),
+                None,
+                Some(render::color::HEAT_GRADIENT[2])
+            ],
+            "{input}"
+        );
+        let blue = image::Rgb([0, 0, 255]);
+        assert_eq!(
+            overlay.color(&root, Path::new("src/main.rs"), 3),
+            Some(blue)
+        );
+        assert_eq!(
+            overlay
+                .legend
+                .iter()
+                .map(|(_, label)| label.as_str())
+                .collect::<Vec<_>>(),
+            ["0.5", "0.62", "0.75", "0.88", "1", "team-a"]
+        );
+    }
+}
+
+#[test]
+fn diagnostics_of_huge_spans_keep_the_most_severe_one_per_line() {
+    let path = std::env::temp_dir().join(format!("codevis-spans-{}.json", std::process::id()));
+    let message = |level: &str, first: u32, last: u32| {
+        format!(
+            r#"{{"reason":"compiler-message","message":{{"level":"{level}","spans":[{{"file_name":"src/lib.rs","line_start":{first},"line_end":{last},"is_primary":true}}]}}}}"#
+        )
+    };
+    std::fs::write(
+        &path,
+        [
+            message("error", 10, 20),
+            message("warning", 1, 4_000_000_000),
+            message("note", 15, 30),
+        ]
+        .join("\n"),
+    )
+    .unwrap();
+    let overlay = diagnostics::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let root = RootPaths::new(Path::new("/home/user/codevis"));
+    let (warning, error) = (
+        Some(diagnostics::Severity::Warning.color()),
+        Some(diagnostics::Severity::Error.color()),
+    );
+    assert_eq!(
+        [1, 10, 20, 21, 30, 4_000_000_000, 4_000_000_001].map(|line| overlay.color(
+            &root,
+            Path::new("src/lib.rs"),
+            line
+        )),
+        [warning, error, error, warning, warning, warning, None]
+    );
+}
+
+#[test]
+fn annotations_of_huge_ranges_take_no_space_per_line() {
+    let path = std::env::temp_dir().join(format!("codevis-ranges-{}.csv", std::process::id()));
+    std::fs::write(
+        &path,
+        "src/lib.rs,1,4000000000,1\nsrc/lib.rs,2,2,#0000ff\nsrc/lib.rs,10,3000000000,2\n",
+    )
+    .unwrap();
+    let overlay = annotations::load(
+        &path,
+        &render::color::HEAT_GRADIENT,
+        codevis::overlay::Scale::Linear,
+    )
+    .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let root = RootPaths::new(Path::new("/home/user/codevis"));
+    let lib = Path::new("src/lib.rs");
+    let (low, high) = (
+        render::color::HEAT_GRADIENT[0],
+        render::color::HEAT_GRADIENT[2],
+    );
+    assert_eq!(
+        [
+            1,
+            2,
+            3,
+            10,
+            3_000_000_000,
+            3_000_000_001,
+            4_000_000_000,
+            4_000_000_001
+        ]
+        .map(|line| overlay.color(&root, lib, line)),
+        [
+            Some(low),
+            Some(image::Rgb([0, 0, 255])),
+            Some(low),
+            Some(high),
+            Some(high),
+            Some(low),
+            Some(low),
+            None
+        ],
+        "later annotations replace the overlapping lines of earlier ones"
+    );
+}
+
+#[test]
+fn blame_colors_lines_by_their_top_authors() {
+    let dir = std::env::temp_dir().join(format!("codevis-blame-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(["-c", "commit.gpgsign=false"])
+            .args(args)
+            .current_dir(&dir)
+            .stdout(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?}");
+    };
+    let commit = |author: &str, file: &str, content: &str| {
+        std::fs::write(dir.join(file), content).unwrap();
+        git(&["add", file]);
+        git(&[
+            "-c",
+            &format!("user.name={author}"),
+            "-c",
+            "user.email=author@example.com",
+            "commit",
+            "--quiet",
+            "-m",
+            file,
+        ]);
+    };
+    git(&["init", "--quiet"]);
+    commit("Alice", "a.rs", "1\n2\n3\n");
+    commit("Bob", "a.rs", "1\nb\n3\n");
+    commit("Carol", "b.rs", "c\n");
+    std::fs::write(dir.join("untracked.rs"), "u\n").unwrap();
+
+    let (contents, _ignored) = codevis::unicode_content(
+        &dir,
+        &Default::default(),
+        prodash::progress::Discard,
+        &Default::default(),
+    )
+    .unwrap();
+    let overlay = blame::load(
+        &contents,
+        1,
+        2,
+        prodash::progress::Discard,
+        &Default::default(),
+    )
+    .unwrap();
+    let root = RootPaths::new(&dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let alice = render::color::palette(0);
+    assert_eq!(
+        [1, 2, 3].map(|line| overlay.color(&root, Path::new("a.rs"), line)),
+        [Some(alice), Some(blame::OTHER), Some(alice)]
+    );
+    assert_eq!(
+        overlay.color(&root, Path::new("b.rs"), 1),
+        Some(blame::OTHER)
+    );
+    assert_eq!(overlay.color(&root, Path::new("untracked.rs"), 1), None);
+    assert_eq!(
+        overlay.legend,
+        [
+            (alice, "Alice (2 lines)".into()),
+            (blame::OTHER, "2 other authors (2 lines)".into())
+        ]
+    );
+}
+
+#[test]
+fn palette_colors_stay_distinct_beyond_the_palette() {
+    let colors: std::collections::HashSet<_> = (0..256).map(render::color::palette).collect();
+    assert_eq!(colors.len(), 256);
+    assert!(!colors.contains(&blame::OTHER));
+}
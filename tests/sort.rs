@@ -0,0 +1,86 @@
+use codevis::sort::{sort, Order};
+use codevis::{content::File, DirContents, Root};
+use std::path::{Path, PathBuf};
+use syntect::parsing::SyntaxSet;
+
+fn sorted(order: Order, list: Option<&Path>) -> Vec<String> {
+    let mut dir_contents = DirContents {
+        roots: vec![Root::new("root".into())],
+        children_content: [
+            ("root/c.rs", "fn main() {}\n"),
+            ("root/b.md", "# Title\n\nText\n"),
+            ("root/a.rs", "fn a() {}\nfn b() {}\n"),
+            (
+                "root/d.unknown",
+                "some text that is longer than the other files\n",
+            ),
+        ]
+        .into_iter()
+        .map(|(path, content)| File::in_memory(PathBuf::from(path), content.to_owned()))
+        .collect(),
+        generated: Default::default(),
+        binary: Default::default(),
+        truncated: Default::default(),
+    };
+    sort(
+        &mut dir_contents,
+        order,
+        &SyntaxSet::load_defaults_newlines(),
+        list,
+    )
+    .unwrap();
+    dir_contents
+        .children_content
+        .into_iter()
+        .map(|file| {
+            file.path
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect()
+}
+
+#[test]
+fn files_are_ordered_by_the_chosen_strategy_with_ties_broken_by_path() {
+    assert_eq!(
+        sorted(Order::Path, None),
+        ["a.rs", "b.md", "c.rs", "d.unknown"]
+    );
+    assert_eq!(
+        sorted(Order::Size, None),
+        ["d.unknown", "a.rs", "b.md", "c.rs"]
+    );
+    assert_eq!(
+        sorted(Order::Lines, None),
+        ["b.md", "a.rs", "c.rs", "d.unknown"]
+    );
+    assert_eq!(
+        sorted(Order::Language, None),
+        ["b.md", "a.rs", "c.rs", "d.unknown"],
+        "Markdown sorts before Rust, and files without syntax come last"
+    );
+    assert_eq!(
+        sorted(Order::List, Some(Path::new("tests/fixtures/sort.list"))),
+        ["b.md", "a.rs", "c.rs", "d.unknown"]
+    );
+}
+
+#[test]
+fn sorting_by_list_requires_a_list() {
+    let mut dir_contents = DirContents {
+        roots: vec![Root::new("root".into())],
+        children_content: Vec::new(),
+        generated: Default::default(),
+        binary: Default::default(),
+        truncated: Default::default(),
+    };
+    assert!(sort(
+        &mut dir_contents,
+        Order::List,
+        &SyntaxSet::load_defaults_newlines(),
+        None
+    )
+    .is_err());
+}
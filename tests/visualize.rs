@@ -12,12 +12,12 @@ fn various_renders() {
 
     let (paths, ignored) = codevis::unicode_content(
         Path::new("./src/"),
-        &[],
+        &Default::default(),
         prodash::progress::Discard,
         &AtomicBool::default(),
     )
     .unwrap();
-    assert_eq!(ignored, 0, "no ignore pattern configured");
+    assert_eq!(ignored.extension, 0, "no ignore pattern configured");
 
     let theme = "Solarized (dark)";
     let mut opts = render::Options {
@@ -38,6 +38,17 @@ fn various_renders() {
         readable: false,
         show_filenames: false,
         line_nums: false,
+        depth_gradient: &render::color::HEAT_GRADIENT,
+        gradient_max_depth: 8,
+        caption: None,
+        highlight_patterns: &[],
+        highlight_whole_lines: false,
+        focus: None,
+        line_overlays: &[],
+        code_owners: &[],
+        replacement_color: None,
+        dim_generated: None,
+        separate_roots: false,
     };
     codevis::render(
         &paths,
@@ -97,12 +108,12 @@ fn multi_threading_produces_same_result_as_single_threaded_mode() {
     let ts = ThemeSet::load_defaults();
     let (paths, ignored) = codevis::unicode_content(
         Path::new("./src/"),
-        &[],
+        &Default::default(),
         prodash::progress::Discard,
         &AtomicBool::default(),
     )
     .unwrap();
-    assert_eq!(ignored, 0, "no ignore pattern configured");
+    assert_eq!(ignored.extension, 0, "no ignore pattern configured");
 
     let theme = "Solarized (light)";
     let mut opts = render::Options {
@@ -123,6 +134,17 @@ fn multi_threading_produces_same_result_as_single_threaded_mode() {
         readable: false,
         show_filenames: false,
         line_nums: false,
+        depth_gradient: &render::color::HEAT_GRADIENT,
+        gradient_max_depth: 8,
+        caption: None,
+        highlight_patterns: &[],
+        highlight_whole_lines: false,
+        focus: None,
+        line_overlays: &[],
+        code_owners: &[],
+        replacement_color: None,
+        dim_generated: None,
+        separate_roots: false,
     };
     let expected = codevis::render(
         &paths,
@@ -149,3 +171,328 @@ fn multi_threading_produces_same_result_as_single_threaded_mode() {
         "multi-threaded version should be pixel-perfect"
     );
 }
+
+type Image = image::ImageBuffer<image::Rgb<u8>, memmap2::MmapMut>;
+
+/// Read the fixtures in `tests/fixtures/render/<root>` for each of `roots`, in order.
+fn fixture(roots: &[&str]) -> codevis::DirContents {
+    let mut contents = codevis::DirContents::default();
+    for root in roots {
+        let (root_contents, _ignored) = codevis::unicode_content(
+            &Path::new("tests/fixtures/render").join(root),
+            &Default::default(),
+            prodash::progress::Discard,
+            &AtomicBool::default(),
+        )
+        .unwrap();
+        contents.append(root_contents);
+    }
+    contents
+}
+
+/// Render `contents` with `opts` on a single thread, and assert that multiple threads produce the same image.
+fn try_render_image(
+    contents: &codevis::DirContents,
+    opts: render::Options<'_>,
+) -> anyhow::Result<Image> {
+    let ss = SyntaxSet::load_defaults_newlines();
+    let ts = ThemeSet::load_defaults();
+    let render = |threads| {
+        codevis::render(
+            contents,
+            prodash::progress::Discard,
+            &AtomicBool::default(),
+            &ss,
+            &ts,
+            render::Options { threads, ..opts },
+        )
+    };
+    let img = render(1)?;
+    assert!(
+        render(2)?.as_bytes() == img.as_bytes(),
+        "multi-threaded version should be pixel-perfect"
+    );
+    Ok(img)
+}
+
+fn render_image(contents: &codevis::DirContents, opts: render::Options<'_>) -> Image {
+    try_render_image(contents, opts).unwrap()
+}
+
+#[test]
+fn caption_adds_a_band_without_touching_the_code() {
+    let contents = fixture(&["app"]);
+    let mut opts = render::Options {
+        plain: true,
+        ..Default::default()
+    };
+    let plain = render_image(&contents, opts);
+
+    for position in [
+        render::caption::Position::Top,
+        render::caption::Position::Bottom,
+    ] {
+        opts.caption = Some(render::Caption {
+            position,
+            title: Some("codevis"),
+            repo_name: "codevis",
+            date: "2022-10-31",
+            scale: 2,
+        });
+        let captioned = render_image(&contents, opts);
+        assert_eq!(captioned.width(), plain.width());
+        assert!(captioned.height() > plain.height());
+
+        let band_height = captioned.height() - plain.height();
+        let code_rows = match position {
+            render::caption::Position::Top => band_height..captioned.height(),
+            render::caption::Position::Bottom => 0..plain.height(),
+        };
+        let row_bytes = plain.width() as usize * 3;
+        assert!(
+            captioned.as_raw()[code_rows.start as usize * row_bytes..][..plain.as_raw().len()]
+                == plain.as_raw()[..],
+            "the code is left as is"
+        );
+    }
+}
+
+#[test]
+fn highlight_patterns_tint_matches() {
+    let contents = fixture(&["app"]);
+    let mut opts = render::Options {
+        plain: true,
+        ..Default::default()
+    };
+    let plain = render_image(&contents, opts);
+
+    let patterns = [render::search::Pattern {
+        regex: regex::Regex::new("unwrap\\(\\)").unwrap(),
+        color: image::Rgb([255, 0, 0]),
+    }];
+    opts.highlight_patterns = &patterns;
+    assert!(
+        render_image(&contents, opts).as_bytes() != plain.as_bytes(),
+        "matches are tinted"
+    );
+}
+
+#[test]
+fn focus_dims_files_out_of_focus() {
+    let contents = fixture(&["app"]);
+    let mut opts = render::Options {
+        plain: true,
+        ..Default::default()
+    };
+    let plain = render_image(&contents, opts);
+
+    let everything = render::focus::Focus::new(&["**".into()], Vec::new(), 0.75).unwrap();
+    opts.focus = Some(&everything);
+    assert!(
+        render_image(&contents, opts).as_bytes() == plain.as_bytes(),
+        "files in focus are untouched"
+    );
+
+    let ui_module = render::focus::Focus::new(&[], vec!["./ui".into()], 0.75).unwrap();
+    opts.focus = Some(&ui_module);
+    assert!(render_image(&contents, opts).as_bytes() != plain.as_bytes());
+}
+
+#[test]
+fn owner_backgrounds_require_code_owners() {
+    let contents = fixture(&["app"]);
+    let mut opts = render::Options {
+        plain: true,
+        bg_color: render::BgColor::Owner,
+        ..Default::default()
+    };
+    assert!(
+        try_render_image(&contents, opts).is_err(),
+        "owners are needed to pick colors"
+    );
+
+    let code_owners = [Some(
+        codevis::codeowners::CodeOwners::from_path(
+            Path::new("tests/fixtures/CODEOWNERS"),
+            Path::new("."),
+            "tests/fixtures/render/app".into(),
+        )
+        .unwrap(),
+    )];
+    opts.code_owners = &code_owners;
+    render_image(&contents, opts);
+}
+
+#[test]
+fn replacement_characters_can_stand_out() {
+    let contents = codevis::DirContents {
+        roots: vec![codevis::Root::new("root".into())],
+        children_content: vec![codevis::content::File::in_memory(
+            "root/lib.rs".into(),
+            "// caf\u{FFFD}\nfn main() {}\n".into(),
+        )],
+        generated: Default::default(),
+        binary: Default::default(),
+        truncated: Default::default(),
+    };
+    let mut opts = render::Options {
+        plain: true,
+        ..Default::default()
+    };
+    let plain = render_image(&contents, opts);
+    let replacement = image::Rgb([255, 0, 255]);
+    assert!(!plain.pixels().any(|pixel| *pixel == replacement));
+
+    opts.replacement_color = Some(replacement);
+    let marked = render_image(&contents, opts);
+    assert!(marked.pixels().any(|pixel| *pixel == replacement));
+}
+
+#[test]
+fn multiple_roots_are_labeled_and_can_occupy_their_own_region() {
+    let ss = SyntaxSet::load_defaults_newlines();
+    let mut contents = fixture(&["lib", "app"]);
+    codevis::sort::sort(&mut contents, codevis::sort::Order::Path, &ss, None).unwrap();
+    let labels: Vec<_> = contents
+        .children_content
+        .iter()
+        .map(|file| contents.label(file).into_owned())
+        .collect();
+    let first_of_app = labels
+        .iter()
+        .position(|label| label.starts_with("app"))
+        .unwrap();
+    assert!(
+        labels[..first_of_app]
+            .iter()
+            .all(|label| label.starts_with("lib"))
+            && labels[first_of_app..]
+                .iter()
+                .all(|label| label.starts_with("app")),
+        "files are labeled by their root and stay grouped by it, in the order of the roots: {labels:?}"
+    );
+
+    // the first path is of neither root, the others of one root each.
+    let overlays: Vec<_> = [
+        "lib/main.rs",
+        "parse.rs",
+        "tests/fixtures/render/lib/parse.rs",
+        "main.rs",
+        "tests/fixtures/render/app/main.rs",
+    ]
+    .into_iter()
+    .map(|path| {
+        let mut overlay = codevis::overlay::LineOverlay::default();
+        overlay.set_range(Path::new(path), 1, 10, image::Rgb([255, 0, 255]));
+        (path, [overlay])
+    })
+    .collect();
+
+    let mut opts = render::Options {
+        plain: true,
+        ..Default::default()
+    };
+    let joined = render_image(&contents, opts);
+    opts.separate_roots = true;
+    let separated = render_image(&contents, opts);
+    assert_eq!(separated.height(), joined.height());
+    assert!(
+        separated.width() > joined.width(),
+        "roots start in a new column"
+    );
+    let label_line: Vec<_> = (0..opts.column_width)
+        .map(|x| *separated.get_pixel(x, 0))
+        .collect();
+    let (name, rest) = label_line.split_at("lib".len());
+    assert!(
+        name.iter().all(|pixel| *pixel == name[0])
+            && rest.iter().all(|pixel| *pixel == rest[0])
+            && name[0] != rest[0],
+        "without file names, roots start with a line showing their name"
+    );
+
+    let overlaid = |overlays| {
+        render_image(
+            &contents,
+            render::Options {
+                line_overlays: overlays,
+                ..opts
+            },
+        )
+    };
+    for (path, overlays) in &overlays[1..] {
+        assert_ne!(
+            overlaid(overlays).as_bytes(),
+            separated.as_bytes(),
+            "overlays apply to files of any root, by paths relative to it or the repository: {path}"
+        );
+    }
+    assert_eq!(
+        overlaid(&overlays[0].1).as_bytes(),
+        separated.as_bytes(),
+        "paths only match within their root"
+    );
+}
+
+#[test]
+fn depth_gradients_color_nested_lines_differently() {
+    let nested = codevis::DirContents {
+        roots: vec![codevis::Root::new("root".into())],
+        children_content: vec![codevis::content::File::in_memory(
+            "root/lib.rs".into(),
+            "fn f() {\n    {\n        {\n            x\n}}}\n".into(),
+        )],
+        ..Default::default()
+    };
+    for fg_color in [
+        codevis::render::FgColor::IndentationDepth,
+        codevis::render::FgColor::BracketDepth,
+    ] {
+        let opts = render::Options {
+            plain: true,
+            line_height: 1,
+            force_full_columns: false,
+            fg_color,
+            bg_color: codevis::render::BgColor::HelixEditor,
+            ..Default::default()
+        };
+        render_image(&fixture(&["lib"]), opts);
+
+        let img = render_image(&nested, opts);
+        assert_ne!(
+            img.get_pixel(0, 0),
+            img.get_pixel(12, 3),
+            "the deeply nested `x` is colored differently than the shallow `f` with {fg_color:?}"
+        );
+    }
+}
+
+#[test]
+fn complexity_backgrounds_make_branchy_files_redder() {
+    let opts = render::Options {
+        line_height: 1,
+        force_full_columns: false,
+        bg_color: codevis::render::BgColor::Complexity,
+        ..Default::default()
+    };
+    render_image(&fixture(&["lib"]), opts);
+
+    let files = codevis::DirContents {
+        roots: vec![codevis::Root::new("root".into())],
+        children_content: vec![
+            codevis::content::File::in_memory("root/flat.rs".into(), "let a = 1;\n".into()),
+            codevis::content::File::in_memory(
+                "root/branchy.rs".into(),
+                "if a && b || c { while d { for e in f { if g { } else { } } } }\n".into(),
+            ),
+        ],
+        ..Default::default()
+    };
+    let img = render_image(&files, opts);
+    assert_eq!(img.height(), 2, "one line per file in a single column");
+    let (flat, branchy) = (img.get_pixel(99, 0), img.get_pixel(99, 1));
+    assert!(
+        branchy[0] > flat[0],
+        "the branchy file is more complex and thus redder: {branchy:?} vs {flat:?}"
+    );
+}